@@ -5,13 +5,10 @@
 #![allow(unused_assignments)]
 #![allow(unused_must_use)]
 #![allow(dead_code)]
-#![feature(test)]
-extern crate test;
 
 use futures_core::future::BoxFuture;
 use std::any::Any;
 use std::future::Future;
-use test::Bencher;
 
 pub trait QPS {
     fn qps(&self, total: u64);
@@ -24,7 +21,7 @@ impl QPS for std::time::Instant {
         let time = self.elapsed();
         println!(
             "use QPS: {} QPS/s",
-            (total as u128 * 1000000000 as u128 / time.as_nanos() as u128)
+            (total as u128 * 1_000_000_000 / time.as_nanos())
         );
     }
 
@@ -105,3 +102,88 @@ fn bench_pool() {
     };
     block_on(f);
 }
+
+//cargo test --release --package fast_pool --bench raw_performance bench_pool_concurrent --no-fail-fast --  --exact -Z unstable-options --show-output
+#[test]
+fn bench_pool_concurrent() {
+    use fast_pool::{Manager, Pool};
+
+    pub struct TestManager {}
+
+    impl Manager for TestManager {
+        type Connection = i32;
+        type Error = String;
+
+        async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+            Ok(0)
+        }
+
+        async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+    let f = async {
+        let p = Pool::new(TestManager {});
+        let total = 100000;
+        let tasks = 8;
+        let now = std::time::Instant::now();
+        let mut handles = vec![];
+        for _ in 0..tasks {
+            let p = p.clone();
+            handles.push(tokio::spawn(async move {
+                for _ in 0..total / tasks {
+                    let v = p.get().await.unwrap();
+                }
+            }));
+        }
+        for h in handles {
+            h.await.unwrap();
+        }
+        now.time(total);
+        now.qps(total);
+    };
+    block_on(f);
+}
+
+//cargo test --release --package fast_pool --bench raw_performance bench_pool_concurrent_batch_returns --no-fail-fast --  --exact -Z unstable-options --show-output
+#[test]
+fn bench_pool_concurrent_batch_returns() {
+    use fast_pool::{Manager, Pool};
+
+    pub struct TestManager {}
+
+    impl Manager for TestManager {
+        type Connection = i32;
+        type Error = String;
+
+        async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+            Ok(0)
+        }
+
+        async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+    let f = async {
+        let p = Pool::new(TestManager {});
+        p.set_batch_returns(true);
+        let total = 100000;
+        let tasks = 8;
+        let now = std::time::Instant::now();
+        let mut handles = vec![];
+        for _ in 0..tasks {
+            let p = p.clone();
+            handles.push(tokio::spawn(async move {
+                for _ in 0..total / tasks {
+                    let v = p.get().await.unwrap();
+                }
+            }));
+        }
+        for h in handles {
+            h.await.unwrap();
+        }
+        now.time(total);
+        now.qps(total);
+    };
+    block_on(f);
+}
@@ -16,7 +16,7 @@ impl Manager for TestManager {
     async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
         //check should use conn.ping()
         if conn == "error" {
-            return Err(Self::Error::from("error".to_string()));
+            return Err("error".to_string());
         }
         Ok(())
     }
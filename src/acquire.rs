@@ -0,0 +1,83 @@
+use crate::plugin::ManagerFuture;
+use crate::{ConnectionBox, Manager, Pool, PoolError};
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// future of `Pool::acquire()`, a nameable `get_timeout()` to store in struct fields
+/// or poll by hand. only the type is named: it is not allocation free, the acquisition
+/// is boxed once when it is created. it is `Send` when feature `send` is enabled
+pub struct Acquire<'a, M: Manager> {
+    inner: ManagerFuture<'a, Result<ConnectionBox<M>, PoolError<M::Error>>>,
+}
+
+/// future of `Pool::acquire_owned()`, same as `Acquire` without borrowing the pool
+pub struct AcquireOwned<M: Manager + 'static> {
+    inner: ManagerFuture<'static, Result<ConnectionBox<M>, PoolError<M::Error>>>,
+}
+
+impl<M: Manager> Future for Acquire<'_, M> {
+    type Output = Result<ConnectionBox<M>, PoolError<M::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+impl<M: Manager + 'static> Future for AcquireOwned<M> {
+    type Output = Result<ConnectionBox<M>, PoolError<M::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+impl<M: Manager> Debug for Acquire<'_, M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Acquire").finish()
+    }
+}
+
+impl<M: Manager + 'static> Debug for AcquireOwned<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AcquireOwned").finish()
+    }
+}
+
+/// the bounds `acquire()` needs to store the acquisition in `Acquire`, the `Send` ones only
+/// with feature `send`. implemented for every such `Manager`
+#[cfg(feature = "send")]
+pub trait AcquireManager: Manager<Connection: Send, Error: Send> + Send + Sync {}
+
+#[cfg(feature = "send")]
+impl<M: Manager<Connection: Send, Error: Send> + Send + Sync> AcquireManager for M {}
+
+/// the bounds `acquire()` needs to store the acquisition in `Acquire`, the `Send` ones only
+/// with feature `send`. implemented for every such `Manager`
+#[cfg(not(feature = "send"))]
+pub trait AcquireManager: Manager {}
+
+#[cfg(not(feature = "send"))]
+impl<M: Manager> AcquireManager for M {}
+
+impl<M: AcquireManager> Pool<M> {
+    /// same as `get_timeout`, as a named future. the acquisition is boxed once here
+    pub fn acquire(&self, d: Option<Duration>) -> Acquire<'_, M> {
+        Acquire {
+            inner: Box::pin(self.get_timeout(d)),
+        }
+    }
+
+    /// same as `acquire`, owning a clone of the pool
+    pub fn acquire_owned(&self, d: Option<Duration>) -> AcquireOwned<M>
+    where
+        M: 'static,
+    {
+        let pool = self.clone();
+        AcquireOwned {
+            inner: Box::pin(async move { pool.get_timeout(d).await }),
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use crate::plugin::BoxManager;
+use crate::{CheckMode, Manager, PanicPolicy, Pool, PoolError};
+use std::time::Duration;
+
+/// PoolBuilder stack manager wrappers and configure the pool up front, so no early get()
+/// races the `set_*` calls. see `Pool::builder()`.
+/// ```rust
+/// use fast_pool::plugin::{BoxPool, ClosureManager};
+/// use fast_pool::Pool;
+/// use std::time::Duration;
+///
+/// let p: BoxPool<String, String> = Pool::builder(ClosureManager::new(
+///     || async { Ok::<_, String>(String::new()) },
+///     |_: &mut String| async { Ok(()) },
+/// ))
+/// //.wrap(|m| MyRetry::new(m, 3)).wrap(|m| MyMetrics::new(m))
+/// .boxed()
+/// .max_open(8)
+/// .connect_timeout(Duration::from_secs(3))
+/// .build();
+/// ```
+pub struct PoolBuilder<M: Manager> {
+    manager: M,
+    settings: Settings,
+    /// `Pool::set_max_idle_time`, bound where `M` is known to be Send
+    set_max_idle_time: Option<SetMaxIdleTime<M>>,
+}
+
+type SetMaxIdleTime<M> = fn(&Pool<M>, Option<Duration>);
+
+/// settings applied by `PoolBuilder::build()` before the pool is handed out
+#[derive(Debug, Clone, Default)]
+struct Settings {
+    max_open: Option<u64>,
+    connect_timeout: Option<Duration>,
+    check_timeout: Option<Duration>,
+    check_mode: Option<CheckMode>,
+    max_checking: Option<u64>,
+    reuse_wait: Option<Duration>,
+    panic_policy: Option<PanicPolicy>,
+    conn_max_lifetime: Option<Duration>,
+    max_idle_time: Option<Duration>,
+}
+
+impl<M: Manager> PoolBuilder<M> {
+    pub fn new(manager: M) -> Self {
+        Self {
+            manager,
+            settings: Settings::default(),
+            set_max_idle_time: None,
+        }
+    }
+
+    /// wrap the manager built so far, e.g. `.wrap(|m| Plugin::new(m, ...))`.
+    /// the wrappers apply from the inside out, the last one sees the calls first.
+    /// a `max_idle_time()` set before it is dropped, set it after the last `wrap()`
+    pub fn wrap<N: Manager>(self, f: impl FnOnce(M) -> N) -> PoolBuilder<N> {
+        let mut settings = self.settings;
+        settings.max_idle_time = None;
+        PoolBuilder {
+            manager: f(self.manager),
+            settings,
+            set_max_idle_time: None,
+        }
+    }
+
+    /// erase the type of the chain built so far, the pool is a `BoxPool<C, E>`.
+    /// unlike `wrap()` it keeps a `max_idle_time()` set before it
+    pub fn boxed(self) -> PoolBuilder<BoxManager<M::Connection, M::Error>>
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        let max_idle_time = self.settings.max_idle_time;
+        let builder = self.wrap(BoxManager::new);
+        match max_idle_time {
+            Some(d) => builder.max_idle_time(d),
+            None => builder,
+        }
+    }
+
+    /// see `Pool::set_max_open()`, default `default_max_open()`
+    pub fn max_open(mut self, n: u64) -> Self {
+        self.settings.max_open = Some(n);
+        self
+    }
+
+    /// see `Pool::set_connect_timeout()`
+    pub fn connect_timeout(mut self, d: Duration) -> Self {
+        self.settings.connect_timeout = Some(d);
+        self
+    }
+
+    /// see `Pool::set_check_timeout()`
+    pub fn check_timeout(mut self, d: Duration) -> Self {
+        self.settings.check_timeout = Some(d);
+        self
+    }
+
+    /// see `Pool::set_check_mode()`
+    pub fn check_mode(mut self, mode: CheckMode) -> Self {
+        self.settings.check_mode = Some(mode);
+        self
+    }
+
+    /// see `Pool::set_max_checking()`
+    pub fn max_checking(mut self, n: u64) -> Self {
+        self.settings.max_checking = Some(n);
+        self
+    }
+
+    /// see `Pool::set_reuse_wait()`
+    pub fn reuse_wait(mut self, d: Duration) -> Self {
+        self.settings.reuse_wait = Some(d);
+        self
+    }
+
+    /// see `Pool::set_panic_policy()`
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.settings.panic_policy = Some(policy);
+        self
+    }
+
+    /// see `Pool::set_conn_max_lifetime()`
+    pub fn conn_max_lifetime(mut self, d: Duration) -> Self {
+        self.settings.conn_max_lifetime = Some(d);
+        self
+    }
+
+    pub fn manager(&self) -> &M {
+        &self.manager
+    }
+
+    /// the pool with every setting applied, before anyone can get() from it
+    pub fn build(self) -> Pool<M>
+    where
+        M::Connection: Unpin,
+    {
+        let pool = Pool::new(self.manager);
+        let settings = self.settings;
+        if let Some(n) = settings.max_open {
+            pool.set_max_open(n);
+        }
+        if settings.connect_timeout.is_some() {
+            pool.set_connect_timeout(settings.connect_timeout);
+        }
+        if settings.check_timeout.is_some() {
+            pool.set_check_timeout(settings.check_timeout);
+        }
+        if let Some(mode) = settings.check_mode {
+            pool.set_check_mode(mode);
+        }
+        if let Some(n) = settings.max_checking {
+            pool.set_max_checking(n);
+        }
+        if settings.reuse_wait.is_some() {
+            pool.set_reuse_wait(settings.reuse_wait);
+        }
+        if let Some(policy) = settings.panic_policy {
+            pool.set_panic_policy(policy);
+        }
+        if settings.conn_max_lifetime.is_some() {
+            pool.set_conn_max_lifetime(settings.conn_max_lifetime);
+        }
+        if let Some(set_max_idle_time) = self.set_max_idle_time {
+            set_max_idle_time(&pool, settings.max_idle_time);
+        }
+        pool
+    }
+
+    /// `build()`, then wait until `min_idle` connections are connected and idle,
+    /// see `Pool::wait_warm()`
+    pub async fn build_and_warm(
+        self,
+        min_idle: u64,
+        timeout: Duration,
+    ) -> Result<Pool<M>, PoolError<M::Error>>
+    where
+        M::Connection: Unpin,
+    {
+        let pool = self.build();
+        pool.wait_warm(min_idle, timeout).await?;
+        Ok(pool)
+    }
+}
+
+impl<M> PoolBuilder<M>
+where
+    M: Manager + Send + Sync + 'static,
+    M::Connection: Send,
+{
+    /// see `Pool::set_max_idle_time()`. set it after the last `wrap()`, which drops it:
+    /// the sweep is bound to the final manager type
+    pub fn max_idle_time(mut self, d: Duration) -> Self {
+        self.settings.max_idle_time = Some(d);
+        self.set_max_idle_time = Some(|pool, d| pool.set_max_idle_time(d));
+        self
+    }
+}
+
+impl<M: Manager> Pool<M> {
+    /// a `PoolBuilder` starting from `manager`, to compose manager wrappers fluently
+    pub fn builder(manager: M) -> PoolBuilder<M> {
+        PoolBuilder::new(manager)
+    }
+}
+
+/// type state of a setting `StrictPoolBuilder` still requires
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Missing;
+
+/// StrictPoolBuilder only compiles `build()` once a manager and `max_open` are given,
+/// so a pool can't silently run with the default `default_max_open()`.
+/// `PoolBuilder` stays the lenient choice for quick starts.
+/// ```rust
+/// use fast_pool::plugin::ClosureManager;
+/// use fast_pool::StrictPoolBuilder;
+///
+/// let p = StrictPoolBuilder::new()
+///     .manager(ClosureManager::new(
+///         || async { Ok::<_, String>(String::new()) },
+///         |_: &mut String| async { Ok(()) },
+///     ))
+///     .max_open(8)
+///     .build();
+/// ```
+/// without `max_open` it does not compile
+/// ```compile_fail
+/// use fast_pool::plugin::ClosureManager;
+/// use fast_pool::StrictPoolBuilder;
+///
+/// let p = StrictPoolBuilder::new()
+///     .manager(ClosureManager::new(
+///         || async { Ok::<_, String>(String::new()) },
+///         |_: &mut String| async { Ok(()) },
+///     ))
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct StrictPoolBuilder<M, N> {
+    manager: M,
+    max_open: N,
+}
+
+impl StrictPoolBuilder<Missing, Missing> {
+    pub fn new() -> Self {
+        Self {
+            manager: Missing,
+            max_open: Missing,
+        }
+    }
+}
+
+impl Default for StrictPoolBuilder<Missing, Missing> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N> StrictPoolBuilder<Missing, N> {
+    pub fn manager<M: Manager>(self, manager: M) -> StrictPoolBuilder<M, N> {
+        StrictPoolBuilder {
+            manager,
+            max_open: self.max_open,
+        }
+    }
+}
+
+impl<M, N> StrictPoolBuilder<M, N> {
+    /// see `Pool::set_max_open()`
+    pub fn max_open(self, n: u64) -> StrictPoolBuilder<M, u64> {
+        StrictPoolBuilder {
+            manager: self.manager,
+            max_open: n,
+        }
+    }
+}
+
+impl<M: Manager, N> StrictPoolBuilder<M, N> {
+    /// same as `PoolBuilder::wrap()`
+    pub fn wrap<W: Manager>(self, f: impl FnOnce(M) -> W) -> StrictPoolBuilder<W, N> {
+        StrictPoolBuilder {
+            manager: f(self.manager),
+            max_open: self.max_open,
+        }
+    }
+}
+
+impl<M: Manager> StrictPoolBuilder<M, u64> {
+    pub fn build(self) -> Pool<M>
+    where
+        M::Connection: Unpin,
+    {
+        PoolBuilder::new(self.manager)
+            .max_open(self.max_open)
+            .build()
+    }
+}
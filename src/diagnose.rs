@@ -0,0 +1,212 @@
+use crate::{CloseReason, ConnectionState, IdleConnection, Manager, Pool, UNLIMITED};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+
+/// result of one item of `Pool::diagnose()`, ordered from best to worst
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum DiagnosticStatus {
+    Pass,
+    /// works, but deserve a look
+    Warn,
+    Fail,
+}
+
+impl DiagnosticStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticStatus::Pass => "PASS",
+            DiagnosticStatus::Warn => "WARN",
+            DiagnosticStatus::Fail => "FAIL",
+        }
+    }
+}
+
+/// one item of a `Diagnosis`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// "counters", "idle_queue", "connect", "check", "config", "handles" or "lifecycle"
+    pub name: &'static str,
+    pub status: DiagnosticStatus,
+    pub detail: String,
+}
+
+/// report of `Pool::diagnose()`, displayed one item per line for support dumps
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnosis {
+    pub items: Vec<Diagnostic>,
+}
+
+impl Diagnosis {
+    /// the worst status of all items
+    pub fn status(&self) -> DiagnosticStatus {
+        self.items
+            .iter()
+            .map(|v| v.status)
+            .max()
+            .unwrap_or(DiagnosticStatus::Pass)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Diagnostic> {
+        self.items.iter().find(|v| v.name == name)
+    }
+
+    fn push(&mut self, name: &'static str, status: DiagnosticStatus, detail: impl Into<String>) {
+        self.items.push(Diagnostic {
+            name,
+            status,
+            detail: detail.into(),
+        });
+    }
+}
+
+impl Display for Diagnosis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for item in &self.items {
+            writeln!(f, "{}: {} {}", item.name, item.status.as_str(), item.detail)?;
+        }
+        Ok(())
+    }
+}
+
+impl<M: Manager> Pool<M> {
+    /// run a battery of self checks for support tooling: counter invariants, idle queue
+    /// against the accounting, a test connect, a test check and config sanity.
+    /// the test connection is kept idle, a connection failing the test check is closed
+    pub async fn diagnose(&self) -> Diagnosis
+    where
+        M::Error: Display,
+    {
+        use DiagnosticStatus::*;
+        let inner = &self.inner;
+        let mut report = Diagnosis { items: vec![] };
+        inner.flush_batched();
+
+        let config = inner.config();
+        let max_open = config.max_open;
+        let hard_cap = max_open.saturating_add(config.burst);
+        let connections = inner.counters.connections();
+        let created = inner.counters.created.load(Ordering::SeqCst);
+        let closed = inner.counters.closed.total();
+        if created < closed {
+            report.push(
+                "counters",
+                Fail,
+                format!("closed {} more than created {}", closed, created),
+            );
+        } else if connections > hard_cap {
+            //legit while a smaller max_open is applied, connections close as they come back
+            report.push(
+                "counters",
+                Warn,
+                format!("connections {} over max_open {}", connections, max_open),
+            );
+        } else {
+            report.push(
+                "counters",
+                Pass,
+                format!(
+                    "connections {}, created {}, closed {}",
+                    connections, created, closed
+                ),
+            );
+        }
+
+        //the counts are read at once, they only disagree on an accounting bug
+        let (connections, in_use, idle) = inner.connection_counts();
+        if in_use + idle <= connections {
+            report.push(
+                "idle_queue",
+                Pass,
+                format!(
+                    "idle {} + in_use {} <= connections {}",
+                    idle, in_use, connections
+                ),
+            );
+        } else {
+            report.push(
+                "idle_queue",
+                Fail,
+                format!(
+                    "idle {} + in_use {} > connections {}",
+                    idle, in_use, connections
+                ),
+            );
+        }
+
+        let mut fresh = None;
+        match inner.reserve() {
+            Some(mut permit) => match inner.connect().await {
+                Ok(conn) => {
+                    inner.mark_created(&mut permit);
+                    permit.transition(ConnectionState::Idle);
+                    fresh = Some(IdleConnection::new(conn, permit));
+                    report.push("connect", Pass, "connected");
+                }
+                Err(e) => report.push("connect", Fail, e.to_string()),
+            },
+            None => report.push("connect", Warn, "pool is full, skipped"),
+        }
+
+        match fresh.or_else(|| inner.idle_queue.try_recv().ok()) {
+            Some(mut idle) => {
+                inner.acquire_check().await;
+                idle.permit.transition(ConnectionState::Checking);
+                let checked = {
+                    defer!(|| inner.release_check());
+                    inner.check(&mut idle.conn).await
+                };
+                match checked {
+                    Ok(_) => {
+                        idle.permit.transition(ConnectionState::Idle);
+                        let id = idle.permit.id();
+                        _ = inner.push_idle(idle);
+                        report.push("check", Pass, format!("connection {} passed", id));
+                    }
+                    Err(e) => {
+                        inner.close_async(idle, CloseReason::CheckFailed).await;
+                        report.push("check", Fail, e.to_string());
+                    }
+                }
+            }
+            None => report.push("check", Warn, "no idle connection, skipped"),
+        }
+
+        let config = self.config();
+        let max_checking = config.max_checking;
+        if let Err(e) = config.validate() {
+            report.push("config", Fail, e);
+        } else if max_open != UNLIMITED && max_checking > max_open {
+            report.push(
+                "config",
+                Warn,
+                format!(
+                    "max_checking {} over max_open {} has no effect",
+                    max_checking, max_open
+                ),
+            );
+        } else if config.fail_on_starvation && config.starvation_threshold.is_none() {
+            report.push(
+                "config",
+                Warn,
+                "fail_on_starvation without a starvation_threshold has no effect",
+            );
+        } else {
+            report.push("config", Pass, format!("max_open {}", max_open));
+        }
+
+        report.push(
+            "handles",
+            Pass,
+            format!("{} live Pool handles", self.handles()),
+        );
+
+        if inner.shut_down.load(Ordering::SeqCst) {
+            report.push("lifecycle", Warn, "pool is shut down");
+        } else if inner.draining.load(Ordering::SeqCst) {
+            report.push("lifecycle", Warn, "pool is draining");
+        } else {
+            report.push("lifecycle", Pass, "serving");
+        }
+        report
+    }
+}
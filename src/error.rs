@@ -0,0 +1,230 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// the pool's errors, one line each: the `PoolError` variant, its `ErrorCode`, the code
+/// string and the message. `ErrorCode`, `PoolError` and every mapping between them are
+/// generated from this table, with `ConnectFailed`, `CheckFailed` and `InvalidConfig`
+/// (carrying data) written once in the macro, so the two enums can't drift apart
+macro_rules! pool_errors {
+    ($($(#[$doc:meta])* $variant:ident => $code:ident($name:literal, $message:literal),)*) => {
+        /// stable code of every `PoolError`, see `PoolError::code()`.
+        /// every `PoolError` message start with the code, e.g. "POOL_TIMEOUT: get_timeout",
+        /// so it can also be read back from a logged message with `ErrorCode::parse()`
+        #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+        pub enum ErrorCode {
+            /// `Manager::connect` failed
+            ConnectFailed,
+            /// `Manager::check` failed
+            CheckFailed,
+            /// `PoolConfig` rejected by `apply_config`
+            InvalidConfig,
+            $($(#[$doc])* $code,)*
+        }
+
+        impl ErrorCode {
+            pub const ALL: [ErrorCode; 3 + [$($name),*].len()] = [
+                ErrorCode::ConnectFailed,
+                ErrorCode::CheckFailed,
+                ErrorCode::InvalidConfig,
+                $(ErrorCode::$code,)*
+            ];
+
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    ErrorCode::ConnectFailed => "CONNECT_FAILED",
+                    ErrorCode::CheckFailed => "CHECK_FAILED",
+                    ErrorCode::InvalidConfig => "INVALID_CONFIG",
+                    $(ErrorCode::$code => $name,)*
+                }
+            }
+
+            /// full message of this code. a failed connect or check display the `Manager`
+            /// Error after the code instead, an invalid config its reason
+            pub fn message(&self) -> &'static str {
+                match self {
+                    ErrorCode::ConnectFailed => "CONNECT_FAILED: connect failed",
+                    ErrorCode::CheckFailed => "CHECK_FAILED: check failed",
+                    ErrorCode::InvalidConfig => "INVALID_CONFIG: invalid config",
+                    $(ErrorCode::$code => concat!($name, ": ", $message),)*
+                }
+            }
+        }
+
+        /// Error of the pool operations: a condition of the pool itself, or the `Manager` Error
+        /// behind a failed connect or check
+        #[derive(Debug, Clone, Eq, PartialEq)]
+        pub enum PoolError<E> {
+            /// `Manager::connect` failed
+            ConnectFailed(E),
+            /// `Manager::check` failed
+            CheckFailed(E),
+            /// `PoolConfig` rejected by `apply_config`, with the reason
+            InvalidConfig(&'static str),
+            $($(#[$doc])* $variant,)*
+        }
+
+        impl<E> PoolError<E> {
+            pub fn code(&self) -> ErrorCode {
+                match self {
+                    PoolError::ConnectFailed(_) => ErrorCode::ConnectFailed,
+                    PoolError::CheckFailed(_) => ErrorCode::CheckFailed,
+                    PoolError::InvalidConfig(_) => ErrorCode::InvalidConfig,
+                    $(PoolError::$variant => ErrorCode::$code,)*
+                }
+            }
+
+            /// convert the `Manager` Error, e.g. `e.map(MyError::from)`
+            pub fn map<F>(self, f: impl FnOnce(E) -> F) -> PoolError<F> {
+                match self {
+                    PoolError::ConnectFailed(e) => PoolError::ConnectFailed(f(e)),
+                    PoolError::CheckFailed(e) => PoolError::CheckFailed(f(e)),
+                    PoolError::InvalidConfig(reason) => PoolError::InvalidConfig(reason),
+                    $(PoolError::$variant => PoolError::$variant,)*
+                }
+            }
+        }
+    };
+}
+
+pool_errors! {
+    /// `get_timeout` elapsed before a connection was available
+    Timeout => PoolTimeout("POOL_TIMEOUT", "get_timeout"),
+    /// the pool is draining and refuse new acquisitions
+    Draining => PoolDraining("POOL_DRAINING", "pool is draining"),
+    /// the idle queue of the pool is closed
+    PoolClosed => PoolClosed("POOL_CLOSED", "pool is closed"),
+    /// gave up waiting on a starved pool, see `Pool::set_fail_on_starvation`
+    Starved => PoolStarved("POOL_STARVED", "pool starved"),
+    /// `drain_timeout` elapsed with connections still in use
+    DrainTimeout => DrainTimeout("DRAIN_TIMEOUT", "drain_timeout"),
+    /// `resize_and_wait` elapsed with connections still open
+    ResizeTimeout => ResizeTimeout("RESIZE_TIMEOUT", "resize_timeout"),
+    /// `wait_warm` elapsed before enough connections were idle
+    WarmTimeout => WarmTimeout("WARM_TIMEOUT", "wait_warm"),
+    /// `PoolGroup` without pools
+    GroupEmpty => GroupEmpty("GROUP_EMPTY", "pool group is empty"),
+    /// `Manager::connect` took longer than `connect_timeout`
+    ConnectTimeout => ConnectTimeout("CONNECT_TIMEOUT", "connect_timeout"),
+    /// `Manager::check` took longer than `check_timeout`
+    CheckTimeout => CheckTimeout("CHECK_TIMEOUT", "check_timeout"),
+    /// the wait for a connection was cancelled by `Pool::cancel_waiters`
+    Cancelled => Cancelled("CANCELLED", "cancel_waiters"),
+}
+
+impl ErrorCode {
+    /// parse the code at the start of an error message, None for a message without one
+    pub fn parse(message: &str) -> Option<ErrorCode> {
+        let code = message.split_once(':').map_or(message, |(code, _)| code);
+        Self::ALL.iter().copied().find(|v| v.as_str() == code)
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `error_code()` on a `PoolError`, or on its message once logged or turned into
+/// an application error
+pub trait ErrorCodeExt {
+    fn error_code(&self) -> Option<ErrorCode>;
+}
+
+impl<E> ErrorCodeExt for PoolError<E> {
+    fn error_code(&self) -> Option<ErrorCode> {
+        Some(self.code())
+    }
+}
+
+impl ErrorCodeExt for str {
+    fn error_code(&self) -> Option<ErrorCode> {
+        ErrorCode::parse(self)
+    }
+}
+
+impl<E> PoolError<E> {
+    /// where the error came from: a connect (failed or timed out), a check, or the pool
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            PoolError::ConnectFailed(_) | PoolError::ConnectTimeout => ErrorKind::Connect,
+            PoolError::CheckFailed(_) | PoolError::CheckTimeout => ErrorKind::Check,
+            _ => ErrorKind::Pool,
+        }
+    }
+
+    /// the `Manager` Error of a failed connect or check
+    pub fn manager_error(&self) -> Option<&E> {
+        match self {
+            PoolError::ConnectFailed(e) | PoolError::CheckFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn into_manager_error(self) -> Option<E> {
+        match self {
+            PoolError::ConnectFailed(e) | PoolError::CheckFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// the `Manager` Error or the invalid config reason after the code,
+/// the pool's own errors as their `ErrorCode::message()`
+impl<E: Display> Display for PoolError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PoolError::ConnectFailed(e) | PoolError::CheckFailed(e) => {
+                write!(f, "{}: {}", self.code().as_str(), e)
+            }
+            PoolError::InvalidConfig(reason) => {
+                write!(f, "{}: {}", ErrorCode::InvalidConfig.as_str(), reason)
+            }
+            _ => f.write_str(self.code().message()),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for PoolError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.manager_error()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// where a recorded Error came from, see `Pool::errors()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ErrorKind {
+    /// `Manager::connect` failed
+    Connect,
+    /// `Manager::check` failed
+    Check,
+    /// raised by the pool itself
+    Pool,
+}
+
+/// a recent Error, see `Pool::errors()`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ErrorRecord {
+    pub at: SystemTime,
+    pub kind: ErrorKind,
+    /// the connection that failed its check, None otherwise
+    pub connection_id: Option<u64>,
+    pub message: String,
+}
+
+/// a connect failure, check failure or pool error (acquire timeout, ...), see `Pool::set_error_handler()`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ErrorEvent {
+    /// `Pool::id()` of the pool
+    pub pool_id: u64,
+    pub kind: ErrorKind,
+    /// the connection that failed its check, None otherwise
+    pub connection_id: Option<u64>,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// callback receiving every `ErrorEvent` of a pool
+pub type ErrorHandler = Arc<dyn Fn(&ErrorEvent) + Send + Sync>;
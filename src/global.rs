@@ -0,0 +1,108 @@
+use crate::{Manager, Pool};
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use tokio::sync::OnceCell;
+
+/// a pool shared program-wide, initialized once on first use. declare it with `global_pool!`
+/// ```rust
+/// use fast_pool::{global_pool, Manager, Pool};
+///
+/// struct DbManager {}
+///
+/// impl Manager for DbManager {
+///     type Connection = String;
+///     type Error = String;
+///
+///     async fn connect(&self) -> Result<String, String> {
+///         Ok("conn".to_string())
+///     }
+///
+///     async fn check(&self, _conn: &mut String) -> Result<(), String> {
+///         Ok(())
+///     }
+/// }
+///
+/// global_pool!(static POOL: DbManager);
+///
+/// #[tokio::main]
+/// async fn main() {
+///     //configure before publishing, other tasks may get() as soon as init() returns
+///     POOL.init(Pool::builder(DbManager {}).max_open(8).build())
+///         .await
+///         .expect("POOL initialized twice");
+///     let conn = POOL.pool().get().await.unwrap();
+/// }
+/// ```
+pub struct GlobalPool<M: Manager> {
+    cell: OnceCell<Pool<M>>,
+}
+
+impl<M: Manager> GlobalPool<M> {
+    pub const fn new() -> Self {
+        Self {
+            cell: OnceCell::const_new(),
+        }
+    }
+
+    /// publish `pool` if it is not initialized yet. waits for a concurrent `get_or_init()`,
+    /// Err hands `pool` back when another one was published first
+    pub async fn init(&self, pool: Pool<M>) -> Result<&Pool<M>, Pool<M>> {
+        let mut pool = Some(pool);
+        let published = self
+            .cell
+            .get_or_init(|| async { pool.take().unwrap() })
+            .await;
+        match pool {
+            None => Ok(published),
+            Some(pool) => Err(pool),
+        }
+    }
+
+    /// the pool, built by `f` on first call. concurrent callers wait for that one `f`,
+    /// so it can apply a config or warm the pool before anyone gets it
+    pub async fn get_or_init<F, Fut>(&self, f: F) -> &Pool<M>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Pool<M>>,
+    {
+        self.cell.get_or_init(f).await
+    }
+
+    /// the pool, None before it is initialized
+    pub fn get(&self) -> Option<&Pool<M>> {
+        self.cell.get()
+    }
+
+    /// the pool, panics if it is not initialized yet
+    pub fn pool(&self) -> &Pool<M> {
+        self.get()
+            .expect("GlobalPool used before init()/get_or_init()")
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.cell.initialized()
+    }
+}
+
+impl<M: Manager> Default for GlobalPool<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Manager> Debug for GlobalPool<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GlobalPool")
+            .field("pool", &self.cell.get())
+            .finish()
+    }
+}
+
+/// declare a `GlobalPool` static: `global_pool!(pub static POOL: MyManager);`
+#[macro_export]
+macro_rules! global_pool {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $manager:ty) => {
+        $(#[$attr])*
+        $vis static $name: $crate::GlobalPool<$manager> = $crate::GlobalPool::new();
+    };
+}
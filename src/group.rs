@@ -0,0 +1,91 @@
+use crate::{ConnectionBox, Manager, Pool, PoolError};
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// how `PoolGroup` pick the pool for an acquisition
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GroupStrategy {
+    /// the pool with the lowest (in_use + waiting) / max_open (default)
+    LeastLoaded,
+    /// every pool in turn
+    RoundRobin,
+}
+
+/// several pools, for example one per backend shard, acquired from as one
+pub struct PoolGroup<M: Manager> {
+    pools: Vec<Pool<M>>,
+    strategy: AtomicU8,
+    next: AtomicUsize,
+}
+
+impl<M: Manager> PoolGroup<M> {
+    pub fn new(pools: Vec<Pool<M>>) -> Self {
+        Self {
+            pools,
+            strategy: AtomicU8::new(GroupStrategy::LeastLoaded as u8),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn set_strategy(&self, strategy: GroupStrategy) {
+        self.strategy.store(strategy as u8, Ordering::SeqCst);
+    }
+
+    pub fn strategy(&self) -> GroupStrategy {
+        match self.strategy.load(Ordering::SeqCst) {
+            v if v == GroupStrategy::RoundRobin as u8 => GroupStrategy::RoundRobin,
+            _ => GroupStrategy::LeastLoaded,
+        }
+    }
+
+    pub fn pools(&self) -> &[Pool<M>] {
+        &self.pools
+    }
+
+    /// the pool the next acquisition goes to, None if the group is empty
+    pub fn pick(&self) -> Option<&Pool<M>> {
+        match self.strategy() {
+            GroupStrategy::RoundRobin => {
+                if self.pools.is_empty() {
+                    return None;
+                }
+                let i = self.next.fetch_add(1, Ordering::Relaxed) % self.pools.len();
+                self.pools.get(i)
+            }
+            GroupStrategy::LeastLoaded => self.pools.iter().min_by(|a, b| {
+                load_factor(a)
+                    .partial_cmp(&load_factor(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            }),
+        }
+    }
+
+    pub async fn get(&self) -> Result<ConnectionBox<M>, PoolError<M::Error>> {
+        self.get_timeout(None).await
+    }
+
+    pub async fn get_timeout(
+        &self,
+        d: Option<Duration>,
+    ) -> Result<ConnectionBox<M>, PoolError<M::Error>> {
+        match self.pick() {
+            None => Err(PoolError::GroupEmpty),
+            Some(pool) => pool.get_timeout(d).await,
+        }
+    }
+}
+
+impl<M: Manager> Debug for PoolGroup<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PoolGroup")
+            .field("pools", &self.pools)
+            .field("strategy", &self.strategy())
+            .finish()
+    }
+}
+
+fn load_factor<M: Manager>(pool: &Pool<M>) -> f64 {
+    let state = pool.state();
+    (state.in_use + state.waiting) as f64 / state.max_open as f64
+}
@@ -2,179 +2,2750 @@
 
 #[macro_use]
 mod defer;
+#[macro_use]
+mod strict;
+mod acquire;
+mod builder;
+mod diagnose;
+mod error;
+mod extensions;
+mod global;
+mod group;
+mod meter;
+mod partition;
+mod permit;
+pub mod plugin;
+mod queue;
+mod rate;
+mod retry;
+#[cfg(feature = "schedule")]
+mod schedule;
+mod scope;
+#[cfg(feature = "serde")]
+mod serde_duration;
+#[cfg(feature = "signal")]
+mod signal;
+mod split;
+mod stash;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod tuning;
+#[cfg(feature = "config-watch")]
+mod watch;
+
+use queue::{Receiver, RecvError, Sender, TryRecvError};
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub use acquire::{Acquire, AcquireManager, AcquireOwned};
+pub use builder::{Missing, PoolBuilder, StrictPoolBuilder};
+pub use diagnose::{Diagnosis, Diagnostic, DiagnosticStatus};
+pub use error::{
+    ErrorCode, ErrorCodeExt, ErrorEvent, ErrorHandler, ErrorKind, ErrorRecord, PoolError,
+};
+pub use extensions::Extensions;
+pub use global::GlobalPool;
+pub use group::{GroupStrategy, PoolGroup};
+pub use meter::{Meter, UsageLimit};
+pub use partition::PartitionedPool;
+pub use permit::{CloseCounts, CloseReason, ConnectionEvent, ConnectionListener, ConnectionState};
+use permit::{ConnectionPermit, Counters};
+use rate::RollingRate;
+pub use rate::RATE_WINDOW_SECS;
+use retry::RetryBudget;
+#[cfg(feature = "schedule")]
+pub use schedule::{CapacitySchedule, CapacityWindow};
+pub use scope::{Scope, ScopedConnection};
+pub use split::{ReadHalf, Splittable, WriteHalf};
+use stash::ReturnStash;
+pub use stash::RETURN_BATCH;
+use tuning::LatencySamples;
+pub use tuning::TimeoutTuning;
+#[cfg(feature = "config-watch")]
+pub use watch::{read_config_file, ConfigReloadEvent, ConfigReloadListener};
+
+/// Pool have manager, get/get_timeout Connection from Pool
+pub struct Pool<M: Manager> {
+    inner: Arc<PoolInner<M>>,
+}
+
+/// `max_open` of a pool without an upper bound, see `Pool::set_max_open_unlimited()`.
+/// a pool still holds at most 2^21 - 1 connections
+pub const UNLIMITED: u64 = u64::MAX;
+
+/// environment variable overriding the `max_open` of new pools, see `default_max_open()`
+pub const MAX_OPEN_ENV: &str = "FAST_POOL_MAX_OPEN";
+
+/// weight of the newest sample in `State::avg_wait_us`
+const WAIT_EWMA_ALPHA: f64 = 0.2;
+
+/// all state of a pool, shared by its clones and the connections it hands out
+struct PoolInner<M: Manager> {
+    id: u64,
+    /// live `Pool` values, see `Pool::handles()`
+    handles: AtomicU64,
+    /// shared with the disposal thread, so queued goodbyes outlive the pool
+    manager: Arc<M>,
+    idle_queue: IdleQueue<M::Connection>,
+    max_open: CachePadded<AtomicU64>,
+    counters: Arc<Counters>,
+    acquires_in_flight: CachePadded<AtomicU64>,
+    waiting: CachePadded<AtomicU64>,
+    /// EWMA of acquire durations in milliseconds, stored as f64 bits
+    avg_wait: AtomicU64,
+    /// acquisitions folded into `avg_wait`
+    wait_samples: AtomicU64,
+    /// get()/get_timeout() calls by id, once they reached an `AcquirePhase`
+    pending: Mutex<HashMap<u64, PendingAcquire>>,
+    /// get()/get_timeout() calls in `Manager::connect`/`Manager::check`, see `State`
+    connecting: AtomicU64,
+    checking_acquires: AtomicU64,
+    /// bumped and notified by `cancel_waiters()`
+    cancel_generation: AtomicU64,
+    cancelled: tokio::sync::Notify,
+    /// see `set_acquire_audit()`
+    audit_capacity: AtomicU64,
+    audit: Mutex<VecDeque<AcquireRecord>>,
+    /// see `set_error_history()`, errors are only kept once a formatter is set
+    error_capacity: AtomicU64,
+    error_format: RwLock<Option<ErrorFormatFn<M::Error>>>,
+    errors: Mutex<VecDeque<ErrorRecord>>,
+    /// see `set_error_handler()`, also needs `error_format`
+    error_handler: RwLock<Option<ErrorHandler>>,
+    /// finished acquisitions, see `Pool::rates()`
+    acquires: RollingRate,
+    timeouts: RollingRate,
+    check_failure: RwLock<CheckFailureFn<M::Error>>,
+    /// see `set_max_checking()`, 0 means no limit
+    max_checking: AtomicU64,
+    checking: AtomicU64,
+    waiting_check: AtomicU64,
+    /// notified every time a check slot is freed
+    check_released: tokio::sync::Notify,
+    /// tasks in `wait_idle_count` or `Pool::notified()`, `idle_pushed` is only notified
+    /// when there are some
+    idle_watchers: AtomicU64,
+    idle_pushed: tokio::sync::Notify,
+    /// nanos, see `set_check_budget_floor()`
+    check_budget_floor: AtomicU64,
+    /// `LowBudgetAction` as u8
+    low_budget_action: AtomicU8,
+    /// nanos of `CheckMode::IdleTime`, 0 means `CheckMode::Always`
+    check_idle_after: AtomicU64,
+    /// f64 bits of the `CheckMode::Sample` ratio, 1.0 means every acquisition
+    check_sample: AtomicU64,
+    /// an error was recorded since the last passed check, sampling checks every acquisition
+    check_after_error: AtomicBool,
+    /// `PanicPolicy` as u8
+    panic_policy: AtomicU8,
+    /// discarded connections are sent to the disposal thread when `set_background_disposal()`
+    /// is on
+    disposal: RwLock<Option<Disposal<M::Connection>>>,
+    /// see `set_batch_returns()`
+    batch_returns: AtomicBool,
+    stash: ReturnStash<IdleConnection<M::Connection>>,
+    max_check_failures: AtomicU64,
+    idle_decay: AtomicU64,
+    idle_decay_generation: AtomicU64,
+    /// nanos, see `set_max_idle_time()`
+    max_idle_time: AtomicU64,
+    max_idle_time_generation: AtomicU64,
+    /// nanos, see `set_conn_max_lifetime()`
+    conn_max_lifetime: AtomicU64,
+    /// nanos, see `set_reuse_wait()`
+    reuse_wait: AtomicU64,
+    draining: AtomicBool,
+    /// see `Pool::shutdown()`, returned connections are closed
+    shut_down: AtomicBool,
+    /// nanos, see `set_starvation_threshold()`
+    starvation_threshold: AtomicU64,
+    starvation_listener: RwLock<Option<StarvationListener>>,
+    /// nanos, see `set_saturation_alert()`
+    saturation_alert: AtomicU64,
+    saturation_generation: AtomicU64,
+    saturation_listener: RwLock<Option<SaturationListener>>,
+    fail_on_starvation: AtomicBool,
+    /// extra connections allowed past max_open, see `set_burst()`
+    burst: AtomicU64,
+    /// nanos
+    burst_after: AtomicU64,
+    /// see `set_retry_budget()`
+    retry_budget: RetryBudget,
+    /// nanos, 0 means no timeout
+    connect_timeout: AtomicU64,
+    check_timeout: AtomicU64,
+    /// successful connects and checks, see `set_timeout_tuning()`
+    connect_latency: LatencySamples,
+    check_latency: LatencySamples,
+    timeout_tuning: RwLock<Option<TimeoutTuning>>,
+    timeout_tuning_generation: AtomicU64,
+    /// odd while a `write_config()` is in progress, see `config()`
+    config_seq: AtomicU64,
+    config_write: Mutex<()>,
+    /// background tasks started outside a tokio runtime, with their generation.
+    /// they are spawned by the next get()
+    deferred_tasks: Mutex<Vec<(StartTask<M>, u64)>>,
+    has_deferred_tasks: AtomicBool,
+    /// see `set_meter()`
+    meter: RwLock<Option<Arc<dyn Meter>>>,
+    /// units recorded on all connections, see `Pool::usage()`
+    usage: AtomicU64,
+    #[cfg(feature = "test-util")]
+    hooks: Arc<test_util::Hooks>,
+}
+
+impl<M: Manager> PoolInner<M> {
+    /// count a finished acquisition for `Pool::rates()` and remember it in `audit`, if enabled
+    fn audit(&self, start: Instant, permit: Option<&ConnectionPermit>, outcome: AcquireOutcome) {
+        self.acquires.record();
+        if outcome == AcquireOutcome::TimedOut {
+            self.timeouts.record();
+        }
+        let capacity = self.audit_capacity.load(Ordering::Relaxed) as usize;
+        if capacity == 0 {
+            return;
+        }
+        let record = AcquireRecord {
+            at: SystemTime::now(),
+            waited: start.elapsed(),
+            fresh: permit.map(|v| v.checkouts() == 1).unwrap_or_default(),
+            outcome,
+            connection_id: permit.map(|v| v.id()),
+        };
+        let mut audit = self.audit.lock().unwrap();
+        while audit.len() >= capacity {
+            audit.pop_front();
+        }
+        audit.push_back(record);
+    }
+
+    /// remember `e` for `Pool::errors()` and report it to the `ErrorHandler`, if enabled
+    fn record_error(&self, connection_id: Option<u64>, e: &PoolError<M::Error>) {
+        self.check_after_error.store(true, Ordering::Relaxed);
+        let capacity = self.error_capacity.load(Ordering::Relaxed) as usize;
+        let handler = self.error_handler.read().unwrap().clone();
+        if capacity == 0 && handler.is_none() {
+            return;
+        }
+        let Some(format) = *self.error_format.read().unwrap() else {
+            return;
+        };
+        let message = format(e);
+        let kind = e.kind();
+        if let Some(handler) = handler {
+            handler(&ErrorEvent {
+                pool_id: self.id,
+                kind,
+                connection_id,
+                code: e.code(),
+                message: message.clone(),
+            });
+        }
+        if capacity == 0 {
+            return;
+        }
+        let record = ErrorRecord {
+            at: SystemTime::now(),
+            kind,
+            connection_id,
+            message,
+        };
+        let mut errors = self.errors.lock().unwrap();
+        while errors.len() >= capacity {
+            errors.pop_front();
+        }
+        errors.push_back(record);
+    }
+
+    /// fold a finished acquisition into `avg_wait`
+    fn record_wait(&self, waited: Duration) {
+        let ms = waited.as_secs_f64() * 1000.0;
+        let first = self.wait_samples.fetch_add(1, Ordering::Relaxed) == 0;
+        _ = self
+            .avg_wait
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old| {
+                let avg = if first {
+                    ms
+                } else {
+                    let old = f64::from_bits(old);
+                    old + (ms - old) * WAIT_EWMA_ALPHA
+                };
+                Some(avg.to_bits())
+            });
+    }
+
+    /// block until a connection is returned. with a starvation threshold, the oldest waiter
+    /// wakes up every threshold to report (and optionally give up on) a pool stuck at max_open.
+    /// with a burst capacity, a waiter queued longer than `burst_after` connects past max_open
+    async fn wait_idle(
+        &self,
+        waiter: u64,
+    ) -> Result<IdleConnection<M::Connection>, PoolError<M::Error>> {
+        let start = Instant::now();
+        let mut reported = start;
+        let generation = self.cancel_generation.load(Ordering::SeqCst);
+        loop {
+            //a burst slot freed after this point wakes the loop up
+            let released = self.counters.released.notified();
+            let cancelled = self.cancelled.notified();
+            if self.cancel_generation.load(Ordering::SeqCst) != generation {
+                return Err(PoolError::Cancelled);
+            }
+            if let Some(idle) = self.connect_burst(start).await? {
+                return Ok(idle);
+            }
+            let config = self.config();
+            let burst = config.burst;
+            let burst_at = start + config.burst_after;
+            let burst_ready = burst != 0 && Instant::now() >= burst_at;
+            let threshold = config.starvation_threshold;
+            let mut wake = None;
+            if let Some(threshold) = threshold {
+                wake = Some(reported + threshold);
+            }
+            if burst != 0 && !burst_ready {
+                wake = Some(wake.map_or(burst_at, |v: Instant| v.min(burst_at)));
+            }
+            let sleep = tokio::time::sleep_until(wake.unwrap_or_else(Instant::now).into());
+            let received = tokio::select! {
+                biased;
+                received = self.idle_queue.recv_async() => received,
+                _ = released, if burst_ready => continue,
+                _ = cancelled => continue,
+                _ = sleep, if wake.is_some() => {
+                    if threshold.is_some_and(|v| reported.elapsed() >= v) {
+                        reported = Instant::now();
+                        if let Some(event) = self.starvation(waiter) {
+                            self.report_starvation(&event);
+                            if config.fail_on_starvation {
+                                return Err(PoolError::Starved);
+                            }
+                        }
+                    }
+                    continue;
+                }
+            };
+            return received.map_err(|_e| PoolError::PoolClosed);
+        }
+    }
+
+    /// connect past max_open, up to `burst` extra connections, once waited `burst_after`
+    async fn connect_burst(
+        &self,
+        since: Instant,
+    ) -> Result<Option<IdleConnection<M::Connection>>, PoolError<M::Error>> {
+        let config = self.config();
+        if config.burst == 0 || since.elapsed() < config.burst_after {
+            return Ok(None);
+        }
+        let hard_cap = config.max_open.saturating_add(config.burst);
+        let Some(mut permit) = ConnectionPermit::reserve(&self.counters, hard_cap) else {
+            return Ok(None);
+        };
+        let conn = self
+            .connect()
+            .await
+            .inspect_err(|e| self.record_error(None, e))?;
+        self.mark_created(&mut permit);
+        permit.transition(ConnectionState::Idle);
+        self.notify_idle_watchers();
+        Ok(Some(IdleConnection::new(conn, permit)))
+    }
+
+    /// remember the get() `id` started at `started` is now in `stage`, see `Pool::pending_acquires()`
+    fn enter_stage(&self, id: u64, started: Instant, stage: AcquirePhase) {
+        let since = Instant::now();
+        self.pending
+            .lock()
+            .unwrap()
+            .entry(id)
+            .and_modify(|v| {
+                v.stage = stage;
+                v.since = since;
+            })
+            .or_insert(PendingAcquire {
+                started,
+                stage,
+                since,
+            });
+    }
+
+    /// Some if every connection is in use and `waiter` is the oldest waiter
+    fn starvation(&self, waiter: u64) -> Option<StarvationEvent> {
+        let max_open = self.max_open.load(Ordering::SeqCst);
+        let in_use = self.counters.in_use();
+        if in_use < max_open {
+            return None;
+        }
+        let pending = self.pending.lock().unwrap();
+        let waiters = pending
+            .iter()
+            .filter(|(_, v)| v.stage == AcquirePhase::Wait);
+        let (oldest, since) = waiters
+            .clone()
+            .map(|(id, v)| (id, v.since))
+            .min_by_key(|(_, since)| *since)?;
+        if *oldest != waiter {
+            return None;
+        }
+        Some(StarvationEvent {
+            pool_id: self.id,
+            max_open,
+            in_use,
+            waiting: waiters.count() as u64,
+            oldest_wait: since.elapsed(),
+        })
+    }
+
+    fn report_starvation(&self, event: &StarvationEvent) {
+        #[cfg(feature = "log")]
+        log::warn!(
+            target: "fast_pool",
+            "pool {:x} starved: all {} connections in use, {} tasks waiting, oldest for {:?}. holders waiting on the same pool deadlock it",
+            event.pool_id,
+            event.in_use,
+            event.waiting,
+            event.oldest_wait
+        );
+        let listener = *self.starvation_listener.read().unwrap();
+        if let Some(listener) = listener {
+            listener(event);
+        }
+    }
+
+    /// tasks are waiting and every connection is in use
+    fn is_saturated(&self) -> bool {
+        self.waiting.load(Ordering::SeqCst) > 0
+            && self.counters.in_use() >= self.max_open.load(Ordering::SeqCst)
+    }
+
+    fn report_saturation(&self, event: &SaturationEvent) {
+        #[cfg(feature = "log")]
+        match event {
+            SaturationEvent::Saturated { pool_id, duration } => {
+                log::warn!(target: "fast_pool", "pool {:x} saturated for {:?}", pool_id, duration)
+            }
+            SaturationEvent::Recovered { pool_id, duration } => {
+                log::info!(target: "fast_pool", "pool {:x} recovered after {:?} saturated", pool_id, duration)
+            }
+        }
+        let listener = *self.saturation_listener.read().unwrap();
+        if let Some(listener) = listener {
+            listener(event);
+        }
+    }
+
+    /// store several settings at once, `config()` never sees half of them
+    fn write_config(&self, f: impl FnOnce()) {
+        let _lock = self.config_write.lock().unwrap_or_else(|e| e.into_inner());
+        self.config_seq.fetch_add(1, Ordering::SeqCst);
+        defer!(|| {
+            self.config_seq.fetch_add(1, Ordering::SeqCst);
+        });
+        f();
+    }
+
+    /// snapshot of the settings, retried while a `write_config()` is in progress
+    fn config(&self) -> PoolConfig {
+        loop {
+            let seq = self.config_seq.load(Ordering::SeqCst);
+            if seq & 1 == 0 {
+                let config = self.load_config();
+                if self.config_seq.load(Ordering::SeqCst) == seq {
+                    return config;
+                }
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    fn load_config(&self) -> PoolConfig {
+        let duration = |v: &AtomicU64| match v.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        };
+        let sample = f64::from_bits(self.check_sample.load(Ordering::SeqCst));
+        let (retry_budget, retry_per_token) = self.retry_budget.get();
+        PoolConfig {
+            max_open: self.max_open.load(Ordering::SeqCst),
+            max_check_failures: self.max_check_failures.load(Ordering::SeqCst),
+            idle_decay: duration(&self.idle_decay),
+            max_idle_time: duration(&self.max_idle_time),
+            conn_max_lifetime: duration(&self.conn_max_lifetime),
+            reuse_wait: duration(&self.reuse_wait),
+            starvation_threshold: duration(&self.starvation_threshold),
+            fail_on_starvation: self.fail_on_starvation.load(Ordering::SeqCst),
+            saturation_alert: duration(&self.saturation_alert),
+            max_checking: self.max_checking.load(Ordering::SeqCst),
+            check_budget_floor: duration(&self.check_budget_floor),
+            low_budget_action: self.low_budget_action(),
+            burst: self.burst.load(Ordering::SeqCst),
+            burst_after: Duration::from_nanos(self.burst_after.load(Ordering::SeqCst)),
+            retry_budget,
+            retry_per_token,
+            connect_timeout: duration(&self.connect_timeout),
+            check_timeout: duration(&self.check_timeout),
+            check_mode: match self.check_idle_after.load(Ordering::SeqCst) {
+                0 if sample < 1.0 => CheckMode::Sample(sample),
+                0 => CheckMode::Always,
+                n => CheckMode::IdleTime(Duration::from_nanos(n)),
+            },
+            panic_policy: self.panic_policy(),
+        }
+    }
+
+    fn panic_policy(&self) -> PanicPolicy {
+        match self.panic_policy.load(Ordering::SeqCst) {
+            v if v == PanicPolicy::Discard as u8 => PanicPolicy::Discard,
+            v if v == PanicPolicy::Check as u8 => PanicPolicy::Check,
+            _ => PanicPolicy::Recycle,
+        }
+    }
+
+    fn low_budget_action(&self) -> LowBudgetAction {
+        match self.low_budget_action.load(Ordering::SeqCst) {
+            v if v == LowBudgetAction::FailFast as u8 => LowBudgetAction::FailFast,
+            _ => LowBudgetAction::SkipCheck,
+        }
+    }
+
+    fn store_check_mode(&self, mode: CheckMode) {
+        let (nanos, sample) = match mode {
+            CheckMode::Always => (0, 1.0),
+            CheckMode::IdleTime(d) => ((d.as_nanos() as u64).max(1), 1.0),
+            CheckMode::Sample(ratio) if ratio.is_nan() => (0, 1.0),
+            CheckMode::Sample(ratio) => (0, ratio.clamp(0.0, 1.0)),
+        };
+        self.check_sample
+            .store(f64::to_bits(sample), Ordering::SeqCst);
+        self.check_idle_after.store(nanos, Ordering::SeqCst);
+    }
+
+    fn store_burst(&self, extra: u64, after: Duration) {
+        self.burst_after
+            .store(after.as_nanos() as u64, Ordering::SeqCst);
+        self.burst.store(extra, Ordering::SeqCst);
+    }
+
+    /// start a new generation of a background task, which stops the one started before.
+    /// outside a tokio runtime it is deferred to the next get()
+    fn start_task(self: &Arc<Self>, generation: &AtomicU64, start: StartTask<M>) {
+        let generation = generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if tokio::runtime::Handle::try_current().is_ok() {
+            start(self, generation);
+            return;
+        }
+        self.deferred_tasks
+            .lock()
+            .unwrap()
+            .push((start, generation));
+        self.has_deferred_tasks.store(true, Ordering::SeqCst);
+    }
+
+    /// spawn the tasks deferred by `start_task()`, once inside a tokio runtime
+    fn start_deferred_tasks(self: &Arc<Self>) {
+        if !self.has_deferred_tasks.load(Ordering::Relaxed)
+            || tokio::runtime::Handle::try_current().is_err()
+        {
+            return;
+        }
+        self.has_deferred_tasks.store(false, Ordering::SeqCst);
+        let tasks = std::mem::take(&mut *self.deferred_tasks.lock().unwrap());
+        for (start, generation) in tasks {
+            start(self, generation);
+        }
+    }
+
+    /// see `Pool::set_idle_decay()`
+    fn run_idle_decay(self: &Arc<Self>, generation: u64)
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        let Some(interval) = self.config().idle_decay else {
+            return;
+        };
+        if self.idle_decay_generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let pool = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                //pool dropped
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                if pool.idle_decay_generation.load(Ordering::SeqCst) != generation {
+                    break;
+                }
+                pool.flush_batched();
+                if let Ok(idle) = pool.idle_queue.try_recv() {
+                    pool.close_spawned(vec![idle], CloseReason::IdleDecay).await;
+                }
+            }
+        });
+    }
+
+    /// see `Pool::set_max_idle_time()`
+    fn run_max_idle_time(self: &Arc<Self>, generation: u64)
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        let Some(d) = self.config().max_idle_time else {
+            return;
+        };
+        if self.max_idle_time_generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let interval = (d / 2).max(Duration::from_millis(1));
+        let pool = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                //pool dropped
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                if pool.max_idle_time_generation.load(Ordering::SeqCst) != generation {
+                    break;
+                }
+                pool.flush_batched();
+                //one turn of the queue, younger connections go back in the same order
+                let mut closing = vec![];
+                for _ in 0..pool.idle_queue.len() {
+                    let Ok(idle) = pool.idle_queue.try_recv() else {
+                        break;
+                    };
+                    if idle.idle_since.elapsed() >= d {
+                        closing.push(idle);
+                    } else {
+                        _ = pool.push_idle(idle);
+                    }
+                }
+                pool.close_spawned(closing, CloseReason::IdleTimeout).await;
+            }
+        });
+    }
+
+    /// see `Pool::set_saturation_alert()`
+    fn run_saturation_alert(self: &Arc<Self>, generation: u64)
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        let Some(d) = self.config().saturation_alert else {
+            return;
+        };
+        if self.saturation_generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let interval = (d / 10).max(Duration::from_millis(1));
+        let pool = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut since: Option<Instant> = None;
+            let mut alerted = false;
+            loop {
+                tokio::time::sleep(interval).await;
+                //pool dropped
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                if pool.saturation_generation.load(Ordering::SeqCst) != generation {
+                    break;
+                }
+                if pool.is_saturated() {
+                    let start = *since.get_or_insert_with(Instant::now);
+                    if !alerted && start.elapsed() >= d {
+                        alerted = true;
+                        pool.report_saturation(&SaturationEvent::Saturated {
+                            pool_id: pool.id,
+                            duration: start.elapsed(),
+                        });
+                    }
+                } else if let Some(start) = since.take() {
+                    if alerted {
+                        alerted = false;
+                        pool.report_saturation(&SaturationEvent::Recovered {
+                            pool_id: pool.id,
+                            duration: start.elapsed(),
+                        });
+                    }
+                }
+            }
+        });
+    }
+
+    /// close idle connections over `max_open`, in use ones are closed when returned
+    fn shrink_idle(&self, max_open: u64) {
+        self.flush_batched();
+        while self.counters.connections() > max_open {
+            match self.idle_queue.try_recv() {
+                Ok(idle) => self.close(idle, CloseReason::Resized),
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// less than the check budget floor is left before `deadline`
+    fn low_budget(&self, deadline: Option<Instant>) -> bool {
+        let floor = self.check_budget_floor.load(Ordering::Relaxed);
+        match deadline {
+            Some(deadline) if floor != 0 => {
+                deadline.saturating_duration_since(Instant::now()) < Duration::from_nanos(floor)
+            }
+            _ => false,
+        }
+    }
+
+    /// wait for a free check slot if `max_checking` is set, release it by `release_check()`
+    async fn acquire_check(&self) {
+        if self.try_check_slot() {
+            return;
+        }
+        self.waiting_check.fetch_add(1, Ordering::SeqCst);
+        defer!(|| {
+            self.waiting_check.fetch_sub(1, Ordering::SeqCst);
+        });
+        loop {
+            let released = self.check_released.notified();
+            if self.try_check_slot() {
+                return;
+            }
+            released.await;
+        }
+    }
+
+    fn try_check_slot(&self) -> bool {
+        let max = self.max_checking.load(Ordering::SeqCst);
+        self.checking
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                if max == 0 || n < max {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .is_ok()
+    }
+
+    fn release_check(&self) {
+        self.checking.fetch_sub(1, Ordering::SeqCst);
+        self.check_released.notify_waiters();
+    }
+
+    /// put a connection in the idle queue, waking `wait_idle_count` callers (the
+    /// `wait_warm`/`wait_for_idle` futures). false if the queue is closed, the connection is dropped
+    fn push_idle(&self, idle: IdleConnection<M::Connection>) -> bool {
+        strict!(
+            idle.permit.pool_id() == self.id,
+            "connection {} of pool {:x} returned to pool {:x}",
+            idle.permit.id(),
+            idle.permit.pool_id(),
+            self.id
+        );
+        strict!(
+            idle.permit.state() == Some(ConnectionState::Idle),
+            "connection {} put in the idle queue while {:?}",
+            idle.permit.id(),
+            idle.permit.state()
+        );
+        if let Some(idle) = self.idle_queue.send(idle) {
+            self.close(idle, CloseReason::Discarded);
+            return false;
+        }
+        self.notify_idle_watchers();
+        true
+    }
+
+    /// return a connection to the pool. with batched returns it is parked on a per thread
+    /// stash, unless someone waits for it
+    fn recycle(&self, idle: IdleConnection<M::Connection>) {
+        if !self.batch_returns.load(Ordering::Relaxed) || self.has_waiters() {
+            let pushed = self.push_idle(idle);
+            strict!(pushed, "connection recycled into a closed pool");
+            return;
+        }
+        self.stash.push(idle, |v| _ = self.push_idle(v));
+        //a waiter showing up meanwhile flushes after registering, or is seen here
+        if self.has_waiters() || !self.batch_returns.load(Ordering::SeqCst) {
+            self.flush_returns();
+        }
+    }
+
+    fn has_waiters(&self) -> bool {
+        self.waiting.load(Ordering::SeqCst) != 0 || self.idle_watchers.load(Ordering::SeqCst) != 0
+    }
+
+    /// move the connections parked by batched returns to the idle queue
+    fn flush_returns(&self) {
+        self.stash.drain(|v| _ = self.push_idle(v));
+    }
+
+    /// `flush_returns` if returns are batched, before looking at the idle queue
+    fn flush_batched(&self) {
+        if self.batch_returns.load(Ordering::SeqCst) {
+            self.flush_returns();
+        }
+    }
+
+    fn notify_idle_watchers(&self) {
+        if self.idle_watchers.load(Ordering::SeqCst) != 0 {
+            self.idle_pushed.notify_waiters();
+        }
+    }
+
+    /// wait until at least `n` connections are idle. with `connect`, new connections are
+    /// created (and checked) while there is room, instead of only waiting for returns
+    async fn wait_idle_count(&self, n: u64, connect: bool) -> Result<(), PoolError<M::Error>> {
+        self.idle_watchers.fetch_add(1, Ordering::SeqCst);
+        defer!(|| {
+            self.idle_watchers.fetch_sub(1, Ordering::SeqCst);
+        });
+        loop {
+            let pushed = self.idle_pushed.notified();
+            let released = self.counters.released.notified();
+            self.flush_batched();
+            if self.idle_queue.len() as u64 >= n {
+                return Ok(());
+            }
+            if let Some(mut permit) = connect.then(|| self.reserve()).flatten() {
+                let mut conn = self
+                    .connect()
+                    .await
+                    .inspect_err(|e| self.record_error(None, e))?;
+                self.acquire_check().await;
+                let checked = {
+                    defer!(|| self.release_check());
+                    self.check(&mut conn).await
+                };
+                checked.inspect_err(|e| self.record_error(Some(permit.id()), e))?;
+                self.mark_created(&mut permit);
+                permit.transition(ConnectionState::Idle);
+                _ = self.push_idle(IdleConnection::new(conn, permit));
+                continue;
+            }
+            tokio::select! {
+                _ = pushed => {}
+                //a freed slot leaves room to connect
+                _ = released, if connect => {}
+            }
+        }
+    }
+
+    /// (connections, in_use, idle) read at once, see `Counters::counts`
+    fn connection_counts(&self) -> (u64, u64, u64) {
+        self.counters.snapshot()
+    }
+
+    /// `Manager::connect` within `connect_timeout`
+    async fn connect(&self) -> Result<M::Connection, PoolError<M::Error>> {
+        let start = Instant::now();
+        let conn = match self.connect_timeout.load(Ordering::Relaxed) {
+            0 => self.manager.connect().await,
+            n => tokio::time::timeout(Duration::from_nanos(n), self.manager.connect())
+                .await
+                .map_err(|_e| PoolError::ConnectTimeout)?,
+        }
+        .map_err(PoolError::ConnectFailed)?;
+        self.connect_latency.record(start.elapsed());
+        Ok(conn)
+    }
+
+    /// `Manager::check` within `check_timeout`
+    async fn check(&self, conn: &mut M::Connection) -> Result<(), PoolError<M::Error>> {
+        let start = Instant::now();
+        match self.check_timeout.load(Ordering::Relaxed) {
+            0 => self.manager.check(conn).await,
+            n => tokio::time::timeout(Duration::from_nanos(n), self.manager.check(conn))
+                .await
+                .map_err(|_e| PoolError::CheckTimeout)?,
+        }
+        .map_err(PoolError::CheckFailed)?;
+        self.check_latency.record(start.elapsed());
+        Ok(())
+    }
+
+    /// take a slot for a new connection, None if the pool is full
+    fn reserve(&self) -> Option<ConnectionPermit> {
+        ConnectionPermit::reserve(&self.counters, self.max_open.load(Ordering::SeqCst))
+    }
+
+    /// creation timestamps are only taken while a max lifetime is set
+    fn needs_timestamp(&self) -> bool {
+        self.conn_max_lifetime.load(Ordering::Relaxed) != 0
+    }
+
+    /// the connection of `permit` was just connected
+    fn mark_created(&self, permit: &mut ConnectionPermit) {
+        if self.needs_timestamp() {
+            permit.stamp_created();
+        }
+        permit.transition(ConnectionState::Created);
+    }
+
+    /// the connection outlived `set_conn_max_lifetime()`
+    fn expired(&self, permit: &ConnectionPermit) -> bool {
+        let max = self.conn_max_lifetime.load(Ordering::Relaxed);
+        max != 0
+            && permit
+                .created_at()
+                .is_some_and(|v| v.elapsed() >= Duration::from_nanos(max))
+    }
+
+    /// discard a connection on a sync path. its `Manager::disconnect` goes to the disposal
+    /// thread, without one it is not called: there is nothing to await it here
+    fn close(&self, idle: IdleConnection<M::Connection>, reason: CloseReason) {
+        let IdleConnection { conn, permit, .. } = idle;
+        if let Some(disposal) = self.disposal.read().unwrap().as_ref() {
+            _ = disposal.send.send(conn);
+        }
+        permit.close(reason);
+    }
+
+    /// discard a connection on an async path, awaiting its `Manager::disconnect`
+    /// unless the disposal thread takes it
+    async fn close_async(&self, idle: IdleConnection<M::Connection>, reason: CloseReason) {
+        let IdleConnection { conn, permit, .. } = idle;
+        let conn = match self.disposal.read().unwrap().as_ref() {
+            Some(disposal) => disposal.send.send(conn).err().map(|e| e.0),
+            None => Some(conn),
+        };
+        if let Some(conn) = conn {
+            self.manager.disconnect(conn).await;
+        }
+        permit.close(reason);
+    }
+
+    /// `close_async()` from a spawned task. the `Manager` futures need not be Send,
+    /// so the goodbyes run on a blocking thread driven by the current runtime
+    async fn close_spawned(
+        self: &Arc<Self>,
+        closing: Vec<IdleConnection<M::Connection>>,
+        reason: CloseReason,
+    ) where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        if closing.is_empty() {
+            return;
+        }
+        let pool = self.clone();
+        let handle = tokio::runtime::Handle::current();
+        _ = tokio::task::spawn_blocking(move || {
+            handle.block_on(async {
+                for idle in closing {
+                    pool.close_async(idle, reason).await;
+                }
+            })
+        })
+        .await;
+    }
+}
+
+impl<M: Manager> Drop for PoolInner<M> {
+    fn drop(&mut self) {
+        //the last handle is gone, idle connections still get their goodbye.
+        //the disposal thread stops once they are done, with the channel dropped after this
+        self.flush_returns();
+        while let Ok(idle) = self.idle_queue.try_recv() {
+            self.close(idle, CloseReason::Discarded);
+        }
+    }
+}
+
+/// keep a hot atomic on its own cache line,
+/// so acquirers and recyclers touching different counters don't false share
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Debug> Debug for CachePadded<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// idle connection with the bookkeeping the pool keeps for it
+struct IdleConnection<C> {
+    conn: C,
+    permit: ConnectionPermit,
+    /// consecutive check failures, reset by a passed check
+    check_failures: u64,
+    /// see `ConnectionBox::health()`
+    health: f64,
+    /// see `ConnectionBox::connection_extensions()`
+    extensions: Extensions,
+    /// when it was last returned, see `CheckMode::IdleTime`
+    idle_since: Instant,
+    /// returned by a panicking thread, checked whatever the `CheckMode`
+    must_check: bool,
+    /// see `ConnectionBox::usage()`
+    usage: u64,
+}
+
+impl<C> IdleConnection<C> {
+    fn new(conn: C, permit: ConnectionPermit) -> Self {
+        Self {
+            conn,
+            permit,
+            check_failures: 0,
+            health: 1.0,
+            extensions: Extensions::new(),
+            idle_since: Instant::now(),
+            must_check: false,
+            usage: 0,
+        }
+    }
+
+    /// a passed check moves health toward 1.0, slowed down by the check latency
+    fn check_passed(&mut self, latency: Duration) {
+        let score = 1.0 / (1.0 + latency.as_secs_f64() * 1000.0 / SLOW_CHECK_MS);
+        self.health += (score - self.health) * HEALTH_ALPHA;
+    }
+
+    fn check_failed(&mut self) {
+        self.health /= 2.0;
+    }
+}
+
+/// the idle queue, counting its connections in `Counters` along with the other counts
+struct IdleQueue<C> {
+    send: Sender<IdleConnection<C>>,
+    recv: Receiver<IdleConnection<C>>,
+    counters: Arc<Counters>,
+}
+
+impl<C> IdleQueue<C> {
+    fn new(counters: Arc<Counters>) -> Self {
+        let (send, recv) = queue::unbounded();
+        Self {
+            send,
+            recv,
+            counters,
+        }
+    }
+
+    /// the connection back if the queue is closed
+    fn send(&self, idle: IdleConnection<C>) -> Option<IdleConnection<C>> {
+        //counted first, a receiver taking it right away never sees the count go below 0
+        self.counters.enter_idle();
+        let e = self.send.send(idle).err()?;
+        self.counters.leave_idle();
+        Some(e.0)
+    }
+
+    fn try_recv(&self) -> Result<IdleConnection<C>, TryRecvError> {
+        self.recv
+            .try_recv()
+            .inspect(|_v| self.counters.leave_idle())
+    }
+
+    async fn recv_async(&self) -> Result<IdleConnection<C>, RecvError> {
+        self.recv
+            .recv_async()
+            .await
+            .inspect(|_v| self.counters.leave_idle())
+    }
+
+    fn len(&self) -> usize {
+        self.send.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.recv.is_empty()
+    }
+}
+
+impl<C> Debug for IdleQueue<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdleQueue")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// the disposal thread of `Pool::set_background_disposal()`, awaiting `Manager::disconnect`
+/// for the connections sent to it. it stops once the sender is dropped and every goodbye is done
+struct Disposal<C> {
+    send: tokio::sync::mpsc::UnboundedSender<C>,
+    /// goodbyes awaited at once
+    limit: Arc<AtomicUsize>,
+    /// a goodbye finished or the limit changed
+    changed: Arc<tokio::sync::Notify>,
+}
+
+impl<C: Send + 'static> Disposal<C> {
+    fn spawn<M>(manager: Arc<M>, limit: usize) -> Self
+    where
+        M: Manager<Connection = C> + Send + Sync + 'static,
+    {
+        let (send, mut recv) = tokio::sync::mpsc::unbounded_channel::<C>();
+        let disposal = Self {
+            send,
+            limit: Arc::new(AtomicUsize::new(limit.max(1))),
+            changed: Arc::new(tokio::sync::Notify::new()),
+        };
+        let (limit, changed) = (disposal.limit.clone(), disposal.changed.clone());
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build the disposal runtime");
+        std::thread::spawn(move || {
+            let goodbyes = tokio::task::LocalSet::new();
+            goodbyes.spawn_local(async move {
+                let running = Rc::new(Cell::new(0usize));
+                while let Some(conn) = recv.recv().await {
+                    while running.get() >= limit.load(Ordering::SeqCst) {
+                        changed.notified().await;
+                    }
+                    running.set(running.get() + 1);
+                    let (manager, running, changed) =
+                        (manager.clone(), running.clone(), changed.clone());
+                    tokio::task::spawn_local(async move {
+                        manager.disconnect(conn).await;
+                        running.set(running.get() - 1);
+                        changed.notify_one();
+                    });
+                }
+            });
+            //until the channel is closed and every goodbye is done
+            rt.block_on(goodbyes);
+        });
+        disposal
+    }
+
+    fn set_limit(&self, limit: usize) {
+        self.limit.store(limit.max(1), Ordering::SeqCst);
+        self.changed.notify_one();
+    }
+}
+
+/// below this health the acquire path looks at one more idle connection
+const HEALTHY: f64 = 0.9;
+/// weight of the newest check in the health score
+const HEALTH_ALPHA: f64 = 0.2;
+/// a check taking this long scores 0.5
+const SLOW_CHECK_MS: f64 = 100.0;
+
+impl<M: Manager> Debug for Pool<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pool")
+            .field("id", &self.inner.id)
+            // .field("manager", &self.manager)
+            .field("max_open", &self.inner.max_open)
+            .field("in_use", &self.inner.counters.in_use())
+            .finish()
+    }
+}
+
+impl<M: Manager> Pool<M> {
+    /// a new handle on an existing pool, counted in `handles()`
+    fn from_inner(inner: Arc<PoolInner<M>>) -> Self {
+        inner.handles.fetch_add(1, Ordering::SeqCst);
+        Self { inner }
+    }
+}
+
+impl<M: Manager> Clone for Pool<M> {
+    fn clone(&self) -> Self {
+        Self::from_inner(self.inner.clone())
+    }
+}
+
+impl<M: Manager> Drop for Pool<M> {
+    fn drop(&mut self) {
+        self.inner.handles.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// What the pool does with a connection when `Manager::check` return Error
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FailureAction {
+    /// drop the connection and get another one (default)
+    DropAndRetry,
+    /// drop the connection and return the check Error to the caller
+    DropAndFail,
+    /// put the connection back to idle and return the check Error to the caller
+    KeepAndFail,
+}
+
+/// what get_timeout() does when less than the check budget floor is left before its deadline,
+/// see `Pool::set_check_budget_floor`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LowBudgetAction {
+    /// hand the connection out without checking it (default)
+    SkipCheck,
+    /// put the connection back to idle and fail with `PoolError::Timeout` right away
+    FailFast,
+}
+
+/// which connections get_timeout() checks before handing them out, see `Pool::set_check_mode`
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CheckMode {
+    /// every connection (default)
+    Always,
+    /// only the ones idle for longer than this since they were created or last returned
+    IdleTime(#[cfg_attr(feature = "serde", serde(with = "serde_duration"))] Duration),
+    /// a random fraction in [0, 1] of the acquisitions, 1 is `Always`. after an error, and for
+    /// connections that failed a check or must be checked, every acquisition until a check passes
+    Sample(f64),
+}
+
+//the ratio compares by bits, so a mode always equals itself
+impl PartialEq for CheckMode {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CheckMode::Always, CheckMode::Always) => true,
+            (CheckMode::IdleTime(a), CheckMode::IdleTime(b)) => a == b,
+            (CheckMode::Sample(a), CheckMode::Sample(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for CheckMode {}
+
+/// what happens to a connection returned while its thread is panicking, it may be left
+/// half way through a request or a transaction. see `Pool::set_panic_policy`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PanicPolicy {
+    /// close it with `CloseReason::Panicked`
+    Discard,
+    /// recycle it, the next get() checks it whatever the `CheckMode`
+    Check,
+    /// recycle it like any other (default, as before the policy existed)
+    Recycle,
+}
+
+/// callback classify a failed check, `PoolError::CheckFailed` or `PoolError::CheckTimeout`,
+/// into a `FailureAction`
+pub type CheckFailureFn<E> = fn(&PoolError<E>) -> FailureAction;
+
+/// `ToString` of a `PoolError` with a `Display` manager Error, see `Pool::set_error_history()`
+type ErrorFormatFn<E> = fn(&PoolError<E>) -> String;
+
+/// spawn a background task of the pool, see `PoolInner::start_task()`
+type StartTask<M> = fn(&Arc<PoolInner<M>>, u64);
+
+/// the steps of get()/get_timeout()
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum AcquirePhase {
+    /// creating a new connection with `Manager::connect`
+    Connect,
+    /// blocked until a connection is returned
+    Wait,
+    /// validating the connection with `Manager::check`
+    Check,
+}
+
+/// Manager create Connection and check Connection
+#[cfg(not(feature = "send"))]
+pub trait Manager {
+    type Connection;
+
+    type Error;
+
+    ///create Connection and check Connection
+    async fn connect(&self) -> Result<Self::Connection, Self::Error>;
+    ///check Connection is alive? if not return Error(Connection will be drop)
+    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error>;
+    ///say goodbye to a Connection the pool closes, default just drop it. awaited when the pool
+    ///closes it on an async path (get(), the idle sweeps, `Pool::shutdown()`) or on the thread of
+    ///`Pool::set_background_disposal()`. without that thread, a Connection closed on a sync path
+    ///(a returned `ConnectionBox`, `Pool::set_max_open()`, the pool dropped) is dropped without it
+    async fn disconnect(&self, conn: Self::Connection) {
+        drop(conn);
+    }
+}
+
+/// Manager create Connection and check Connection
+///
+/// with feature `send` the returned futures must be `Send`, so `Pool<M>::get()`
+/// can be spawned from code generic over `M`. `async fn` impls keep working unchanged.
+#[cfg(feature = "send")]
+pub trait Manager {
+    type Connection;
+
+    type Error;
+
+    ///create Connection and check Connection
+    fn connect(&self) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send;
+    ///check Connection is alive? if not return Error(Connection will be drop)
+    fn check(
+        &self,
+        conn: &mut Self::Connection,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    ///say goodbye to a Connection the pool closes, default just drop it. awaited when the pool
+    ///closes it on an async path (get(), the idle sweeps, `Pool::shutdown()`) or on the thread of
+    ///`Pool::set_background_disposal()`. without that thread, a Connection closed on a sync path
+    ///(a returned `ConnectionBox`, `Pool::set_max_open()`, the pool dropped) is dropped without it
+    fn disconnect(&self, conn: Self::Connection) -> impl Future<Output = ()> + Send {
+        drop(conn);
+        std::future::ready(())
+    }
+}
+
+impl<M: Manager> Pool<M> {
+    pub fn new(m: M) -> Self
+    where
+        <M as Manager>::Connection: Unpin,
+    {
+        let default_max = default_max_open();
+        let id = new_pool_id();
+        let counters = Arc::new(Counters::new(id));
+        Self {
+            inner: Arc::new(PoolInner {
+                id,
+                handles: AtomicU64::new(1),
+                manager: Arc::new(m),
+                idle_queue: IdleQueue::new(counters.clone()),
+                max_open: CachePadded(AtomicU64::new(default_max)),
+                counters,
+                acquires_in_flight: CachePadded(AtomicU64::new(0)),
+                waiting: CachePadded(AtomicU64::new(0)),
+                avg_wait: AtomicU64::new(0),
+                wait_samples: AtomicU64::new(0),
+                pending: Mutex::new(HashMap::new()),
+                connecting: AtomicU64::new(0),
+                checking_acquires: AtomicU64::new(0),
+                cancel_generation: AtomicU64::new(0),
+                cancelled: tokio::sync::Notify::new(),
+                audit_capacity: AtomicU64::new(0),
+                audit: Mutex::new(VecDeque::new()),
+                error_capacity: AtomicU64::new(0),
+                error_format: RwLock::new(None),
+                errors: Mutex::new(VecDeque::new()),
+                error_handler: RwLock::new(None),
+                acquires: RollingRate::new(),
+                timeouts: RollingRate::new(),
+                check_failure: RwLock::new(|_| FailureAction::DropAndRetry),
+                max_check_failures: AtomicU64::new(0),
+                max_checking: AtomicU64::new(0),
+                checking: AtomicU64::new(0),
+                waiting_check: AtomicU64::new(0),
+                check_released: tokio::sync::Notify::new(),
+                idle_watchers: AtomicU64::new(0),
+                idle_pushed: tokio::sync::Notify::new(),
+                check_budget_floor: AtomicU64::new(0),
+                low_budget_action: AtomicU8::new(LowBudgetAction::SkipCheck as u8),
+                check_idle_after: AtomicU64::new(0),
+                check_sample: AtomicU64::new(1.0f64.to_bits()),
+                check_after_error: AtomicBool::new(false),
+                panic_policy: AtomicU8::new(PanicPolicy::Recycle as u8),
+                disposal: RwLock::new(None),
+                batch_returns: AtomicBool::new(false),
+                stash: ReturnStash::new(),
+                idle_decay: AtomicU64::new(0),
+                idle_decay_generation: AtomicU64::new(0),
+                max_idle_time: AtomicU64::new(0),
+                max_idle_time_generation: AtomicU64::new(0),
+                conn_max_lifetime: AtomicU64::new(0),
+                reuse_wait: AtomicU64::new(0),
+                draining: AtomicBool::new(false),
+                shut_down: AtomicBool::new(false),
+                starvation_threshold: AtomicU64::new(0),
+                starvation_listener: RwLock::new(None),
+                saturation_alert: AtomicU64::new(0),
+                saturation_generation: AtomicU64::new(0),
+                saturation_listener: RwLock::new(None),
+                fail_on_starvation: AtomicBool::new(false),
+                burst: AtomicU64::new(0),
+                burst_after: AtomicU64::new(0),
+                retry_budget: RetryBudget::default(),
+                connect_timeout: AtomicU64::new(0),
+                check_timeout: AtomicU64::new(0),
+                connect_latency: LatencySamples::new(),
+                check_latency: LatencySamples::new(),
+                timeout_tuning: RwLock::new(None),
+                timeout_tuning_generation: AtomicU64::new(0),
+                config_seq: AtomicU64::new(0),
+                config_write: Mutex::new(()),
+                deferred_tasks: Mutex::new(Vec::new()),
+                has_deferred_tasks: AtomicBool::new(false),
+                meter: RwLock::new(None),
+                usage: AtomicU64::new(0),
+                #[cfg(feature = "test-util")]
+                hooks: Default::default(),
+            }),
+        }
+    }
+
+    pub async fn get(&self) -> Result<ConnectionBox<M>, PoolError<M::Error>> {
+        self.get_timeout(None).await
+    }
+
+    /// same as `get_timeout`, with `correlation_id()` returning `id` inside the
+    /// `Manager::connect`/`Manager::check` calls made for this acquisition
+    pub async fn get_with(
+        &self,
+        id: impl Into<String>,
+        d: Option<Duration>,
+    ) -> Result<ConnectionBox<M>, PoolError<M::Error>> {
+        CORRELATION_ID.scope(id.into(), self.get_timeout(d)).await
+    }
+
+    pub async fn get_timeout(
+        &self,
+        d: Option<Duration>,
+    ) -> Result<ConnectionBox<M>, PoolError<M::Error>> {
+        let start = Instant::now();
+        let deadline = d.map(|d| start + d);
+        let acquire = ACQUIRE_ID.fetch_add(1, Ordering::Relaxed);
+        self.inner.start_deferred_tasks();
+        self.inner.acquires_in_flight.fetch_add(1, Ordering::SeqCst);
+        defer!(|| {
+            self.inner.acquires_in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.inner.pending.lock().unwrap().remove(&acquire);
+        });
+        //pop connection from channel
+        let f = async {
+            loop {
+                if self.inner.draining.load(Ordering::SeqCst) {
+                    return Err(PoolError::Draining);
+                }
+                if self.inner.batch_returns.load(Ordering::Relaxed)
+                    && self.inner.idle_queue.is_empty()
+                {
+                    self.inner.flush_returns();
+                }
+                let config = self.inner.config();
+                let unlimited = config.max_open == UNLIMITED;
+                let mut reused = None;
+                if config.reuse_wait.is_some()
+                    && self.inner.idle_queue.is_empty()
+                    && self.inner.counters.in_use() != 0
+                {
+                    //a connection in use may come back soon, give it a chance before connecting
+                    if let Ok(Ok(idle)) = tokio::time::timeout(
+                        config.reuse_wait.unwrap_or_default(),
+                        self.inner.idle_queue.recv_async(),
+                    )
+                    .await
+                    {
+                        reused = Some(idle);
+                    }
+                }
+                //a reuse cache only connects when nothing is idle
+                let permit = if reused.is_some()
+                    || ((unlimited || config.reuse_wait.is_some())
+                        && !self.inner.idle_queue.is_empty())
+                {
+                    None
+                } else {
+                    self.inner.reserve()
+                };
+                if let Some(mut permit) = permit {
+                    #[cfg(feature = "test-util")]
+                    self.inner.hooks.reached(AcquirePhase::Connect).await;
+                    self.inner
+                        .enter_stage(acquire, start, AcquirePhase::Connect);
+                    //create connection,this can limit max idle,current now max idle = max_open
+                    let connected = {
+                        self.inner.connecting.fetch_add(1, Ordering::SeqCst);
+                        defer!(|| {
+                            self.inner.connecting.fetch_sub(1, Ordering::SeqCst);
+                        });
+                        self.inner.connect().await
+                    };
+                    let conn = match connected {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            self.inner.record_error(None, &e);
+                            //try again while the pool-wide budget allows it
+                            if self.inner.retry_budget.withdraw() {
+                                continue;
+                            }
+                            return Err(e);
+                        }
+                    };
+                    self.inner.mark_created(&mut permit);
+                    permit.transition(ConnectionState::Idle);
+                    if !self.inner.push_idle(IdleConnection::new(conn, permit)) {
+                        return Err(PoolError::PoolClosed);
+                    }
+                }
+                let next = match reused {
+                    Some(idle) => Ok(idle),
+                    None => self.inner.idle_queue.try_recv(),
+                };
+                let mut idle = match next {
+                    Ok(idle) => idle,
+                    //another task took it, there is always room for a new one
+                    Err(_) if unlimited => continue,
+                    Err(_) => {
+                        //no idle connection and no room to create one, wait for a return
+                        self.inner.enter_stage(acquire, start, AcquirePhase::Wait);
+                        self.inner.waiting.fetch_add(1, Ordering::SeqCst);
+                        defer!(|| {
+                            self.inner.waiting.fetch_sub(1, Ordering::SeqCst);
+                        });
+                        //parked returns are seen by this waiter, see `recycle()`
+                        self.inner.flush_batched();
+                        #[cfg(feature = "test-util")]
+                        self.inner.hooks.reached(AcquirePhase::Wait).await;
+                        self.inner.wait_idle(acquire).await?
+                    }
+                };
+                //prefer a healthier connection, the flaky one goes to the back of the queue
+                if idle.health < HEALTHY {
+                    if let Ok(other) = self.inner.idle_queue.try_recv() {
+                        if other.health > idle.health {
+                            _ = self.inner.push_idle(std::mem::replace(&mut idle, other));
+                        } else {
+                            _ = self.inner.push_idle(other);
+                        }
+                    }
+                }
+                if self.inner.draining.load(Ordering::SeqCst) {
+                    _ = self.inner.push_idle(idle);
+                    return Err(PoolError::Draining);
+                }
+                if self.inner.expired(&idle.permit) {
+                    self.inner.close_async(idle, CloseReason::Expired).await;
+                    continue;
+                }
+                let skip_check = match self.inner.config().check_mode {
+                    //returned recently, still good
+                    CheckMode::IdleTime(d) => !idle.must_check && idle.idle_since.elapsed() < d,
+                    //not picked this time
+                    CheckMode::Sample(sample) => {
+                        !idle.must_check
+                            && idle.check_failures == 0
+                            && !self.inner.check_after_error.load(Ordering::Relaxed)
+                            && !sampled(sample)
+                    }
+                    CheckMode::Always => false,
+                };
+                if skip_check {
+                    idle.permit.transition(ConnectionState::InUse);
+                    break Ok(idle);
+                }
+                #[cfg(feature = "test-util")]
+                self.inner.hooks.reached(AcquirePhase::Check).await;
+                self.inner.enter_stage(acquire, start, AcquirePhase::Check);
+                //check connection
+                self.inner.acquire_check().await;
+                if !idle.must_check && self.inner.low_budget(deadline) {
+                    self.inner.release_check();
+                    match self.low_budget_action() {
+                        LowBudgetAction::SkipCheck => {
+                            idle.permit.transition(ConnectionState::InUse);
+                            break Ok(idle);
+                        }
+                        LowBudgetAction::FailFast => {
+                            _ = self.inner.push_idle(idle);
+                            return Err(PoolError::Timeout);
+                        }
+                    }
+                }
+                idle.permit.transition(ConnectionState::Checking);
+                let check_start = Instant::now();
+                let checked = {
+                    self.inner.checking_acquires.fetch_add(1, Ordering::SeqCst);
+                    defer!(|| {
+                        self.inner.checking_acquires.fetch_sub(1, Ordering::SeqCst);
+                        self.inner.release_check();
+                    });
+                    self.inner.check(&mut idle.conn).await
+                };
+                match checked {
+                    Ok(_) => {
+                        idle.check_passed(check_start.elapsed());
+                        self.inner.check_after_error.store(false, Ordering::Relaxed);
+                        idle.permit.transition(ConnectionState::InUse);
+                        break Ok(idle);
+                    }
+                    Err(e) => {
+                        self.inner.record_error(Some(idle.permit.id()), &e);
+                        match self.check_failure_action()(&e) {
+                            FailureAction::DropAndRetry => {
+                                self.inner.close_async(idle, CloseReason::CheckFailed).await;
+                                if !self.inner.retry_budget.enabled()
+                                    || self.inner.retry_budget.withdraw()
+                                {
+                                    continue;
+                                }
+                                return Err(e);
+                            }
+                            FailureAction::DropAndFail => {
+                                self.inner.close_async(idle, CloseReason::CheckFailed).await;
+                                return Err(e);
+                            }
+                            FailureAction::KeepAndFail => {
+                                idle.check_failures += 1;
+                                idle.check_failed();
+                                let max = self.inner.max_check_failures.load(Ordering::SeqCst);
+                                if max == 0 || idle.check_failures < max {
+                                    idle.permit.transition(ConnectionState::Idle);
+                                    _ = self.inner.push_idle(idle);
+                                } else {
+                                    self.inner.close_async(idle, CloseReason::CheckFailed).await;
+                                }
+                                return Err(e);
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        let result = match d {
+            None => f.await.map_err(|e| (e, AcquireOutcome::Failed)),
+            Some(d) => match tokio::time::timeout(d, f).await {
+                Ok(result) => result.map_err(|e| (e, AcquireOutcome::Failed)),
+                Err(_) => Err((PoolError::Timeout, AcquireOutcome::TimedOut)),
+            },
+        };
+        let idle = match result {
+            Ok(idle) => idle,
+            Err((e, outcome)) => {
+                //connect and check errors were recorded where they happened
+                if e.kind() == ErrorKind::Pool {
+                    self.inner.record_error(None, &e);
+                }
+                self.inner.audit(start, None, outcome);
+                return Err(e);
+            }
+        };
+        self.inner.record_wait(start.elapsed());
+        self.inner.retry_budget.deposit();
+        self.inner
+            .audit(start, Some(&idle.permit), AcquireOutcome::Ok);
+        Ok(ConnectionBox {
+            inner: Some(idle.conn),
+            permit: Some(idle.permit),
+            health: idle.health,
+            usage: idle.usage,
+            extensions: Extensions::new(),
+            connection_extensions: idle.extensions,
+            pool: self.inner.clone(),
+        })
+    }
+
+    /// pause every acquisition right before `phase` until the returned `PausePoint` release it
+    #[cfg(feature = "test-util")]
+    pub fn pause_at(&self, phase: AcquirePhase) -> test_util::PausePoint {
+        self.inner.hooks.pause_at(phase)
+    }
+
+    /// wait until every connection in use is returned.
+    /// if `stop_acquire` is true, get()/get_timeout() fail from now on
+    pub async fn drain(&self, stop_acquire: bool) {
+        _ = self.drain_timeout(stop_acquire, None).await;
+    }
+
+    /// same as `drain`, but return Error if connections are still in use after `d`
+    pub async fn drain_timeout(
+        &self,
+        stop_acquire: bool,
+        d: Option<Duration>,
+    ) -> Result<(), PoolError<M::Error>> {
+        if stop_acquire {
+            self.inner.draining.store(true, Ordering::SeqCst);
+        }
+        let f = async {
+            loop {
+                let notified = self.inner.counters.drained.notified();
+                if self.inner.counters.in_use() == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        };
+        match d {
+            None => f.await,
+            Some(d) => tokio::time::timeout(d, f)
+                .await
+                .map_err(|_e| PoolError::DrainTimeout)?,
+        }
+        Ok(())
+    }
+
+    /// stop new acquisitions, wait up to `grace` for the connections in use, then close
+    /// every idle connection. connections returned later are closed too.
+    /// return Error if connections were still in use after `grace`
+    pub async fn shutdown(&self, grace: Option<Duration>) -> Result<(), PoolError<M::Error>> {
+        self.inner.shut_down.store(true, Ordering::SeqCst);
+        let drained = self.drain_timeout(true, grace).await;
+        self.inner.flush_returns();
+        while let Ok(idle) = self.inner.idle_queue.try_recv() {
+            self.inner.close_async(idle, CloseReason::Shutdown).await;
+        }
+        drained
+    }
+
+    /// is the pool refusing new acquisitions after `drain(true)`
+    pub fn is_draining(&self) -> bool {
+        self.inner.draining.load(Ordering::SeqCst)
+    }
+
+    /// fail every get()/get_timeout() currently blocked waiting for a connection with
+    /// `PoolError::Cancelled`, for emergency load shedding. the pool keeps serving, connections
+    /// in use or idle are left alone. returns the number of waiters cancelled
+    pub fn cancel_waiters(&self) -> u64 {
+        let waiting = self.inner.waiting.load(Ordering::SeqCst);
+        self.inner.cancel_generation.fetch_add(1, Ordering::SeqCst);
+        self.inner.cancelled.notify_waiters();
+        waiting
+    }
+
+    /// the tasks currently blocked waiting for a connection, longest waiting first
+    pub fn waiters(&self) -> Vec<WaiterInfo> {
+        let mut waiters = self.pending_acquires();
+        waiters.retain(|w| w.stage == AcquirePhase::Wait);
+        waiters.sort_by_key(|w| std::cmp::Reverse(w.waited));
+        waiters
+    }
+
+    /// the get()/get_timeout() calls in progress with the stage they are in, to tell
+    /// queueing from a slow backend connect or check. longest running first
+    pub fn pending_acquires(&self) -> Vec<WaiterInfo> {
+        let now = Instant::now();
+        let mut pending: Vec<WaiterInfo> = self
+            .inner
+            .pending
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, v)| WaiterInfo {
+                id: *id,
+                stage: v.stage,
+                waited: now.saturating_duration_since(v.since),
+                elapsed: now.saturating_duration_since(v.started),
+            })
+            .collect();
+        pending.sort_by_key(|w| std::cmp::Reverse(w.elapsed));
+        pending
+    }
+
+    /// keep the last `n` finished acquisitions for `recent_acquires()`. 0 disable it (default)
+    pub fn set_acquire_audit(&self, n: usize) {
+        self.inner.audit_capacity.store(n as u64, Ordering::SeqCst);
+        let mut audit = self.inner.audit.lock().unwrap();
+        while audit.len() > n {
+            audit.pop_front();
+        }
+        audit.shrink_to(n);
+    }
+
+    /// keep the last `n` connect, check and pool errors for `errors()`. 0 disable it (default)
+    pub fn set_error_history(&self, n: usize)
+    where
+        M::Error: Display,
+    {
+        *self.inner.error_format.write().unwrap() =
+            Some(<PoolError<M::Error> as ToString>::to_string);
+        self.inner.error_capacity.store(n as u64, Ordering::SeqCst);
+        let mut errors = self.inner.errors.lock().unwrap();
+        while errors.len() > n {
+            errors.pop_front();
+        }
+        errors.shrink_to(n);
+    }
+
+    /// set a callback receiving every connect failure, check failure and pool error (acquire
+    /// timeout, ...) as an `ErrorEvent`, for centralized alerting. None remove it.
+    /// it runs inline where the error happened, so keep it cheap
+    pub fn set_error_handler(&self, handler: Option<ErrorHandler>)
+    where
+        M::Error: Display,
+    {
+        *self.inner.error_format.write().unwrap() =
+            Some(<PoolError<M::Error> as ToString>::to_string);
+        *self.inner.error_handler.write().unwrap() = handler;
+    }
+
+    /// the last errors, oldest first, see `set_error_history()`
+    pub fn errors(&self) -> Vec<ErrorRecord> {
+        self.inner.errors.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// acquisitions, connects and timeouts per second over the last `window`,
+    /// rounded to whole seconds and at most `RATE_WINDOW_SECS`
+    pub fn rates(&self, window: Duration) -> Rates {
+        Rates {
+            acquires: self.inner.acquires.per_sec(window),
+            connects: self.inner.counters.connects.per_sec(window),
+            timeouts: self.inner.timeouts.per_sec(window),
+        }
+    }
+
+    /// the last finished acquisitions, oldest first, see `set_acquire_audit()`
+    pub fn recent_acquires(&self) -> Vec<AcquireRecord> {
+        self.inner.audit.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// snapshot of the current effective configuration
+    pub fn config(&self) -> PoolConfig {
+        self.inner.config()
+    }
+
+    /// a `Scope` acquiring from this pool. connections acquired through it and still held
+    /// when it is dropped are returned, a safety net against leaked connections
+    pub fn scope(&self) -> Scope<M> {
+        Scope::new(self.clone())
+    }
+
+    /// same as `config()`, to be stored and given back to `apply_config()`
+    pub fn export_config(&self) -> PoolConfig {
+        self.config()
+    }
+
+    /// apply a full configuration at once. it is validated first, an invalid config
+    /// return Error and change nothing. the settings are swapped together, `config()` and
+    /// acquisitions see either all of the old ones or all of the new ones. background tasks
+    /// outside a tokio runtime start on the next get()
+    pub fn apply_config(&self, config: &PoolConfig) -> Result<(), PoolError<M::Error>>
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        config.validate().map_err(PoolError::InvalidConfig)?;
+        let old = self.inner.config();
+        let inner = &self.inner;
+        inner.write_config(|| {
+            inner.max_open.store(config.max_open, Ordering::SeqCst);
+            inner
+                .max_check_failures
+                .store(config.max_check_failures, Ordering::SeqCst);
+            inner
+                .idle_decay
+                .store(interval_nanos(config.idle_decay), Ordering::SeqCst);
+            inner
+                .max_idle_time
+                .store(interval_nanos(config.max_idle_time), Ordering::SeqCst);
+            inner
+                .saturation_alert
+                .store(interval_nanos(config.saturation_alert), Ordering::SeqCst);
+            self.set_conn_max_lifetime(config.conn_max_lifetime);
+            self.set_reuse_wait(config.reuse_wait);
+            self.set_starvation_threshold(config.starvation_threshold);
+            self.set_fail_on_starvation(config.fail_on_starvation);
+            inner
+                .max_checking
+                .store(config.max_checking, Ordering::SeqCst);
+            self.set_check_budget_floor(config.check_budget_floor);
+            self.set_low_budget_action(config.low_budget_action);
+            inner.store_burst(config.burst, config.burst_after);
+            if old.retry_budget != config.retry_budget
+                || old.retry_per_token != config.retry_per_token.max(1)
+            {
+                inner
+                    .retry_budget
+                    .set(config.retry_budget, config.retry_per_token);
+            }
+            self.set_connect_timeout(config.connect_timeout);
+            self.set_check_timeout(config.check_timeout);
+            inner.store_check_mode(config.check_mode);
+            self.set_panic_policy(config.panic_policy);
+        });
+        //a raised limit lets waiting acquisitions in
+        inner.check_released.notify_waiters();
+        inner.shrink_idle(config.max_open);
+        //restarting the background tasks would reset their timers
+        if old.idle_decay != config.idle_decay {
+            inner.start_task(&inner.idle_decay_generation, PoolInner::run_idle_decay);
+        }
+        if old.max_idle_time != config.max_idle_time {
+            inner.start_task(
+                &inner.max_idle_time_generation,
+                PoolInner::run_max_idle_time,
+            );
+        }
+        if old.saturation_alert != config.saturation_alert {
+            inner.start_task(
+                &inner.saturation_generation,
+                PoolInner::run_saturation_alert,
+            );
+        }
+        Ok(())
+    }
+
+    /// the manager, if it is a `T`. for a `BoxManager` use `BoxManager::downcast_ref`
+    pub fn manager_as<T: Any>(&self) -> Option<&T>
+    where
+        M: 'static,
+    {
+        (&*self.inner.manager as &dyn Any).downcast_ref()
+    }
+
+    /// random id of this pool instance, shared by its clones
+    pub fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    pub fn state(&self) -> State {
+        self.inner.flush_batched();
+        let (connections, in_use, idle) = self.inner.connection_counts();
+        State {
+            id: self.inner.id,
+            max_open: self.inner.max_open.load(Ordering::Relaxed),
+            connections,
+            in_use,
+            idle,
+            acquires_in_flight: self.inner.acquires_in_flight.load(Ordering::Relaxed),
+            waiting: self.inner.waiting.load(Ordering::Relaxed),
+            waiting_check: self.inner.waiting_check.load(Ordering::Relaxed),
+            connecting: self.inner.connecting.load(Ordering::Relaxed),
+            checking: self.inner.checking_acquires.load(Ordering::Relaxed),
+            avg_wait_us: (f64::from_bits(self.inner.avg_wait.load(Ordering::Relaxed)) * 1000.0)
+                .round() as u64,
+            acquires: self.inner.wait_samples.load(Ordering::Relaxed),
+            created: self.inner.counters.created.load(Ordering::Relaxed),
+            closed: self.inner.counters.closed.total(),
+            closed_by: self.inner.counters.closed.snapshot(),
+            handles: self.handles(),
+        }
+    }
+
+    /// set the callback deciding what to do with a connection whose check failed.
+    /// default always return `FailureAction::DropAndRetry`
+    pub fn set_check_failure_action(&self, f: CheckFailureFn<M::Error>) {
+        *self.inner.check_failure.write().unwrap() = f;
+    }
+
+    pub fn check_failure_action(&self) -> CheckFailureFn<M::Error> {
+        *self.inner.check_failure.read().unwrap()
+    }
+
+    /// when a get_timeout() has less than `floor` left before its deadline, a check would likely
+    /// make it time out: apply `low_budget_action()` instead of checking. None disable it (default)
+    pub fn set_check_budget_floor(&self, floor: Option<Duration>) {
+        let nanos = floor.map(|d| (d.as_nanos() as u64).max(1)).unwrap_or(0);
+        self.inner.check_budget_floor.store(nanos, Ordering::SeqCst);
+    }
+
+    pub fn check_budget_floor(&self) -> Option<Duration> {
+        match self.inner.check_budget_floor.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        }
+    }
+
+    /// check every connection (default), only the ones idle for a while, or a random
+    /// fraction of the acquisitions. can be changed at any time
+    pub fn set_check_mode(&self, mode: CheckMode) {
+        self.inner
+            .write_config(|| self.inner.store_check_mode(mode));
+    }
+
+    pub fn check_mode(&self) -> CheckMode {
+        self.inner.config().check_mode
+    }
 
-use flume::{Receiver, Sender};
-use std::fmt::{Debug, Display, Formatter};
-use std::ops::{Deref, DerefMut};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        self.inner
+            .panic_policy
+            .store(policy as u8, Ordering::SeqCst);
+    }
 
-/// Pool have manager, get/get_timeout Connection from Pool
-pub struct Pool<M: Manager> {
-    manager: Arc<M>,
-    idle_send: Arc<Sender<M::Connection>>,
-    idle_recv: Arc<Receiver<M::Connection>>,
-    max_open: Arc<AtomicU64>,
-    in_use: Arc<AtomicU64>,
-    waits: Arc<AtomicU64>,
-}
+    pub fn panic_policy(&self) -> PanicPolicy {
+        self.inner.panic_policy()
+    }
 
-impl<M: Manager> Debug for Pool<M> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Pool")
-            // .field("manager", &self.manager)
-            .field("max_open", &self.max_open)
-            .field("in_use", &self.in_use)
-            .finish()
+    pub fn set_low_budget_action(&self, action: LowBudgetAction) {
+        self.inner
+            .low_budget_action
+            .store(action as u8, Ordering::SeqCst);
     }
-}
 
-impl<M: Manager> Clone for Pool<M> {
-    fn clone(&self) -> Self {
-        Self {
-            manager: self.manager.clone(),
-            idle_send: self.idle_send.clone(),
-            idle_recv: self.idle_recv.clone(),
-            max_open: self.max_open.clone(),
-            in_use: self.in_use.clone(),
-            waits: self.waits.clone(),
+    pub fn low_budget_action(&self) -> LowBudgetAction {
+        self.inner.low_budget_action()
+    }
+
+    /// run at most `n` `Manager::check` at once, other acquisitions wait for a free slot
+    /// (see `State::waiting_check`). 0 means no limit (default)
+    pub fn set_max_checking(&self, n: u64) {
+        self.inner.max_checking.store(n, Ordering::SeqCst);
+        //a raised limit lets waiting acquisitions in
+        self.inner.check_released.notify_waiters();
+    }
+
+    pub fn max_checking(&self) -> u64 {
+        self.inner.max_checking.load(Ordering::SeqCst)
+    }
+
+    /// retire a connection kept by `FailureAction::KeepAndFail` once it failed `n` checks in a row.
+    /// 0 means no limit (default)
+    pub fn set_max_check_failures(&self, n: u64) {
+        self.inner.max_check_failures.store(n, Ordering::SeqCst);
+    }
+
+    pub fn max_check_failures(&self) -> u64 {
+        self.inner.max_check_failures.load(Ordering::SeqCst)
+    }
+
+    /// resolve once at least `min_idle` connections are idle, connecting and checking new ones
+    /// while there is room under max_open. Error if the pool is not warm after `timeout`
+    pub async fn wait_warm(
+        &self,
+        min_idle: u64,
+        timeout: Duration,
+    ) -> Result<(), PoolError<M::Error>> {
+        tokio::time::timeout(timeout, self.inner.wait_idle_count(min_idle, true))
+            .await
+            .map_err(|_e| PoolError::WarmTimeout)?
+    }
+
+    /// await `Manager::disconnect` for closed connections (failed check, resize, idle decay, ...)
+    /// on a disposal thread, at most `limit` goodbyes at once, instead of on the acquire path.
+    /// their slot is freed right away, and connections closed on a sync path get their goodbye
+    /// too. None turns it off (default), see `Manager::disconnect`. the disposal thread runs its own current-thread runtime, so the goodbyes
+    /// need not be Send. the pool keeps one thread, calling it again only changes the limit,
+    /// and connections queued when the pool is dropped still get their goodbye
+    pub fn set_background_disposal(&self, limit: Option<usize>)
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send + 'static,
+    {
+        let mut disposal = self.inner.disposal.write().unwrap();
+        let Some(limit) = limit else {
+            //the thread says goodbye to what is left and stops with the channel
+            *disposal = None;
+            return;
+        };
+        match disposal.as_ref() {
+            Some(disposal) => disposal.set_limit(limit),
+            None => *disposal = Some(Disposal::spawn(self.inner.manager.clone(), limit)),
         }
     }
-}
 
-/// Manager create Connection and check Connection
-pub trait Manager {
-    type Connection;
+    pub fn background_disposal(&self) -> bool {
+        self.inner.disposal.read().unwrap().is_some()
+    }
 
-    type Error: for<'a> From<&'a str>;
+    /// park returned connections on a per thread stash and move them to the idle queue
+    /// `RETURN_BATCH` at a time, so bursts of returns contend less on the queue.
+    /// they go straight to the queue while tasks wait. default false
+    pub fn set_batch_returns(&self, batch: bool) {
+        self.inner.batch_returns.store(batch, Ordering::SeqCst);
+        if !batch {
+            self.inner.flush_returns();
+        }
+    }
 
-    ///create Connection and check Connection
-    async fn connect(&self) -> Result<Self::Connection, Self::Error>;
-    ///check Connection is alive? if not return Error(Connection will be drop)
-    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error>;
-}
+    pub fn batch_returns(&self) -> bool {
+        self.inner.batch_returns.load(Ordering::SeqCst)
+    }
 
-impl<M: Manager> Pool<M> {
-    pub fn new(m: M) -> Self
+    /// resolve the next time a connection is returned to the pool or created, counted from
+    /// this call (not from the first poll). for schedulers reacting to availability
+    pub fn notified(&self) -> impl Future<Output = ()> + '_ {
+        let inner = &self.inner;
+        inner.idle_watchers.fetch_add(1, Ordering::SeqCst);
+        let guard = defer::Guard(Some(move || {
+            inner.idle_watchers.fetch_sub(1, Ordering::SeqCst);
+        }));
+        let notified = inner.idle_pushed.notified();
+        async move {
+            let _guard = guard;
+            notified.await
+        }
+    }
+
+    /// resolve once at least `n` connections are idle, without connecting new ones
+    pub async fn wait_for_idle(&self, n: u64) {
+        _ = self.inner.wait_idle_count(n, false).await;
+    }
+
+    /// visit every connection idle right now, one at a time. the visited connection is out of
+    /// the idle queue while `f` runs, so nobody else can use it. return the number visited
+    pub async fn for_each_idle<F>(&self, mut f: F) -> u64
     where
-        <M as Manager>::Connection: Unpin,
+        F: AsyncFnMut(&mut M::Connection),
     {
-        let default_max = num_cpus::get() as u64;
-        let (s, r) = flume::unbounded();
-        Self {
-            manager: Arc::new(m),
-            idle_send: Arc::new(s),
-            idle_recv: Arc::new(r),
-            max_open: Arc::new(AtomicU64::new(default_max)),
-            in_use: Arc::new(AtomicU64::new(0)),
-            waits: Arc::new(AtomicU64::new(0)),
+        self.inner.flush_batched();
+        let mut visited = 0;
+        for _ in 0..self.inner.idle_queue.len() {
+            let Ok(mut idle) = self.inner.idle_queue.try_recv() else {
+                break;
+            };
+            f(&mut idle.conn).await;
+            _ = self.inner.push_idle(idle);
+            visited += 1;
         }
+        visited
     }
 
-    pub async fn get(&self) -> Result<ConnectionBox<M>, M::Error> {
-        self.get_timeout(None).await
+    /// drain the idle queue and hand the connections over, for a custom teardown or a handoff
+    /// at shutdown instead of letting the pool drop them. they leave the pool accounting
+    /// closed with `CloseReason::Discarded`, connections in use are left alone.
+    /// handed over, not closed: `Manager::disconnect` is not called, the caller owns them
+    pub fn take_all_idle(&self) -> Vec<M::Connection> {
+        self.inner.flush_batched();
+        let mut taken = Vec::with_capacity(self.inner.idle_queue.len());
+        while let Ok(idle) = self.inner.idle_queue.try_recv() {
+            //dropping the permit frees its slot
+            taken.push(idle.conn);
+        }
+        taken
     }
 
-    pub async fn get_timeout(&self, d: Option<Duration>) -> Result<ConnectionBox<M>, M::Error> {
-        self.waits.fetch_add(1, Ordering::SeqCst);
-        defer!(|| {
-            self.waits.fetch_sub(1, Ordering::SeqCst);
-        });
-        //pop connection from channel
-        let f = async {
-            loop {
-                let idle = self.idle_send.len() as u64;
-                let connections = self.in_use.load(Ordering::SeqCst) + idle;
-                if connections < self.max_open.load(Ordering::SeqCst) {
-                    //create connection,this can limit max idle,current now max idle = max_open
-                    let conn = self.manager.connect().await?;
-                    self.idle_send
-                        .send(conn)
-                        .map_err(|e| M::Error::from(&e.to_string()))?;
-                }
-                let mut conn = self
-                    .idle_recv
-                    .recv_async()
-                    .await
-                    .map_err(|e| M::Error::from(&e.to_string()))?;
-                //check connection
-                self.in_use.fetch_add(1, Ordering::SeqCst);
-                match self.manager.check(&mut conn).await {
-                    Ok(_) => {
-                        break Ok(conn);
-                    }
-                    Err(_e) => {
-                        drop(conn);
-                        self.in_use.fetch_sub(1, Ordering::SeqCst);
-                        if false {
-                            return Err(_e);
-                        }
-                        continue;
-                    }
+    /// move idle connections into `other` until `other` reach its max_open,
+    /// connections `other` has no room for stay idle in this pool.
+    /// return the number of moved connections
+    pub fn transfer_idle_to(&self, other: &Pool<M>) -> u64 {
+        if Arc::ptr_eq(&self.inner, &other.inner) {
+            return 0;
+        }
+        self.inner.flush_batched();
+        let mut moved = 0;
+        for _ in 0..self.inner.idle_queue.len() {
+            let Some(permit) = other.inner.reserve() else {
+                break;
+            };
+            match self.inner.idle_queue.try_recv() {
+                Ok(mut idle) => {
+                    //the old permit is dropped, freeing its slot in this pool
+                    idle.permit = idle.permit.transfer(permit);
+                    _ = other.inner.push_idle(idle);
+                    moved += 1;
                 }
+                Err(_) => break,
             }
-        };
-        let conn = {
-            if d.is_none() {
-                f.await?
-            } else {
-                tokio::time::timeout(d.unwrap(), f)
-                    .await
-                    .map_err(|_e| M::Error::from("get_timeout"))??
+        }
+        moved
+    }
+
+    /// number of connections the pool closed for `reason`
+    pub fn closed(&self, reason: CloseReason) -> u64 {
+        self.inner
+            .counters
+            .closed
+            .get(reason)
+            .load(Ordering::SeqCst)
+    }
+
+    /// live `Pool` values sharing this pool: clones held by the application, scopes, groups
+    /// and owned acquisitions. a count that stays up after a subsystem shut down points at
+    /// a component still holding the pool, and its connections
+    pub fn handles(&self) -> u64 {
+        self.inner.handles.load(Ordering::SeqCst)
+    }
+
+    /// number of connections the pool closed, by `CloseReason`
+    pub fn close_counts(&self) -> CloseCounts {
+        self.inner.counters.closed.snapshot()
+    }
+
+    /// set the `Meter` consulted with the usage recorded by `ConnectionBox::record()`,
+    /// a connection it retires is closed when returned. None remove it
+    pub fn set_meter(&self, meter: Option<Arc<dyn Meter>>) {
+        *self.inner.meter.write().unwrap() = meter;
+    }
+
+    pub fn meter(&self) -> Option<Arc<dyn Meter>> {
+        self.inner.meter.read().unwrap().clone()
+    }
+
+    /// units recorded by `ConnectionBox::record()` on all connections of the pool
+    pub fn usage(&self) -> u64 {
+        self.inner.usage.load(Ordering::Relaxed)
+    }
+
+    /// set a callback receiving every `ConnectionEvent` of this pool, None remove it.
+    /// it runs inline on the acquire/return path, so keep it cheap
+    pub fn set_connection_listener(&self, listener: Option<ConnectionListener>) {
+        self.inner.counters.set_listener(listener);
+    }
+
+    /// close at most one idle connection every `interval`, so surplus idle connections
+    /// are shed gradually instead of at once. None disable it (default).
+    /// the decay run on a spawned task, outside a tokio runtime it starts on the next get()
+    pub fn set_idle_decay(&self, interval: Option<Duration>)
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        self.inner
+            .idle_decay
+            .store(interval_nanos(interval), Ordering::SeqCst);
+        self.inner
+            .start_task(&self.inner.idle_decay_generation, PoolInner::run_idle_decay);
+    }
+
+    /// close connections left idle for `d` or longer, before the server kills the session
+    /// and get() hands out a dead connection. the idle queue is swept every `d / 2`,
+    /// so a connection is closed after at most `1.5 * d` idle. None disable it (default).
+    /// the sweep run on a spawned task, outside a tokio runtime it starts on the next get()
+    pub fn set_max_idle_time(&self, d: Option<Duration>)
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        self.inner
+            .max_idle_time
+            .store(interval_nanos(d), Ordering::SeqCst);
+        self.inner.start_task(
+            &self.inner.max_idle_time_generation,
+            PoolInner::run_max_idle_time,
+        );
+    }
+
+    /// report `SaturationEvent::Saturated` once the pool stays saturated (tasks waiting while
+    /// every connection is in use) for `d`, then `SaturationEvent::Recovered` when it is not anymore.
+    /// the pool is sampled every `d / 10`, shorter dips are not seen. None disable it (default).
+    /// the sampling run on a spawned task, outside a tokio runtime it starts on the next get()
+    pub fn set_saturation_alert(&self, d: Option<Duration>)
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        self.inner
+            .saturation_alert
+            .store(interval_nanos(d), Ordering::SeqCst);
+        self.inner.start_task(
+            &self.inner.saturation_generation,
+            PoolInner::run_saturation_alert,
+        );
+    }
+
+    pub fn saturation_alert(&self) -> Option<Duration> {
+        match self.inner.saturation_alert.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        }
+    }
+
+    /// set the callback receiving `SaturationEvent`s, None remove it.
+    /// with feature `log` they are also logged
+    pub fn set_saturation_listener(&self, listener: Option<SaturationListener>) {
+        *self.inner.saturation_listener.write().unwrap() = listener;
+    }
+
+    /// before creating a new connection, reuse an idle one if any, otherwise wait up to `d`
+    /// for a connection in use to be returned. avoid connect churn under bursty load.
+    /// None disable it (default), get() connects whenever the pool has room
+    pub fn set_reuse_wait(&self, d: Option<Duration>) {
+        let nanos = d.map(|d| (d.as_nanos() as u64).max(1)).unwrap_or(0);
+        self.inner.reuse_wait.store(nanos, Ordering::SeqCst);
+    }
+
+    pub fn reuse_wait(&self) -> Option<Duration> {
+        match self.inner.reuse_wait.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        }
+    }
+
+    /// log the pool `State` at info level every `interval`, until the pool is dropped.
+    /// must be called inside a tokio runtime
+    #[cfg(feature = "log")]
+    pub fn spawn_state_logger(&self, interval: Duration) -> tokio::task::JoinHandle<()>
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        let pool = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(inner) = pool.upgrade() else {
+                    break;
+                };
+                let state = Pool::from_inner(inner).state();
+                log::info!(target: "fast_pool", "pool {:x} state: {}", state.id, state);
             }
-        };
-        Ok(ConnectionBox {
-            inner: Some(conn),
-            sender: self.idle_send.clone(),
-            in_use: self.in_use.clone(),
-            max_open: self.max_open.clone(),
         })
     }
 
-    pub fn state(&self) -> State {
-        State {
-            max_open: self.max_open.load(Ordering::Relaxed),
-            connections: self.in_use.load(Ordering::Relaxed) + self.idle_send.len() as u64,
-            in_use: self.in_use.load(Ordering::Relaxed),
-            idle: self.idle_send.len() as u64,
-            waits: self.waits.load(Ordering::Relaxed),
+    /// report a `StarvationEvent` every `d` while the pool stays at max_open in use
+    /// and the same task keeps waiting. None disable it (default)
+    pub fn set_starvation_threshold(&self, d: Option<Duration>) {
+        let nanos = d.map(|d| (d.as_nanos() as u64).max(1)).unwrap_or(0);
+        self.inner
+            .starvation_threshold
+            .store(nanos, Ordering::SeqCst);
+    }
+
+    pub fn starvation_threshold(&self) -> Option<Duration> {
+        match self.inner.starvation_threshold.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        }
+    }
+
+    /// set the callback receiving `StarvationEvent`s, None remove it.
+    /// with feature `log` they are also logged at warn level
+    pub fn set_starvation_listener(&self, listener: Option<StarvationListener>) {
+        *self.inner.starvation_listener.write().unwrap() = listener;
+    }
+
+    /// on starvation, fail the oldest waiter with a "pool starved" Error instead of
+    /// letting it wait forever
+    pub fn set_fail_on_starvation(&self, fail: bool) {
+        self.inner.fail_on_starvation.store(fail, Ordering::SeqCst);
+    }
+
+    pub fn fail_on_starvation(&self) -> bool {
+        self.inner.fail_on_starvation.load(Ordering::SeqCst)
+    }
+
+    /// let max_open be a soft limit: a task waiting longer than `after` may open up to
+    /// `extra` connections more. they are closed instead of recycled once nobody waits.
+    /// 0 extra disable it (default)
+    pub fn set_burst(&self, extra: u64, after: Duration) {
+        self.inner
+            .write_config(|| self.inner.store_burst(extra, after));
+    }
+
+    /// (extra connections, wait before bursting)
+    pub fn burst(&self) -> (u64, Duration) {
+        let config = self.inner.config();
+        (config.burst, config.burst_after)
+    }
+
+    /// share a budget of `max` retries between all acquisitions, refilled by one token
+    /// every `per_token` successful acquisitions. a failed connect is retried while a token
+    /// is left, and `FailureAction::DropAndRetry` fail with the check Error once none is,
+    /// so a struggling backend sees the pool back off instead of every task retrying.
+    /// the bucket starts full. 0 max disable it (default): connect errors are returned
+    /// and failed checks retried without limit
+    pub fn set_retry_budget(&self, max: u64, per_token: u64) {
+        self.inner
+            .write_config(|| self.inner.retry_budget.set(max, per_token));
+    }
+
+    /// (max retries, successes per token)
+    pub fn retry_budget(&self) -> (u64, u64) {
+        let config = self.inner.config();
+        (config.retry_budget, config.retry_per_token)
+    }
+
+    /// retries left in the budget
+    pub fn retry_tokens(&self) -> u64 {
+        self.inner.retry_budget.tokens()
+    }
+
+    /// fail a `Manager::connect` taking longer than `d` with a CONNECT_TIMEOUT Error.
+    /// None disable it (default), see also `set_timeout_tuning()`
+    pub fn set_connect_timeout(&self, d: Option<Duration>) {
+        let nanos = d.map(|d| (d.as_nanos() as u64).max(1)).unwrap_or(0);
+        self.inner.connect_timeout.store(nanos, Ordering::SeqCst);
+    }
+
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        match self.inner.connect_timeout.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        }
+    }
+
+    /// fail a `Manager::check` taking longer than `d` with a CHECK_TIMEOUT Error, handled
+    /// like any failed check. None disable it (default), see also `set_timeout_tuning()`
+    pub fn set_check_timeout(&self, d: Option<Duration>) {
+        let nanos = d.map(|d| (d.as_nanos() as u64).max(1)).unwrap_or(0);
+        self.inner.check_timeout.store(nanos, Ordering::SeqCst);
+    }
+
+    pub fn check_timeout(&self) -> Option<Duration> {
+        match self.inner.check_timeout.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        }
+    }
+
+    pub fn idle_decay(&self) -> Option<Duration> {
+        match self.inner.idle_decay.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        }
+    }
+
+    /// close connections once they are `d` old, on their way back to the pool or before
+    /// they are handed out. only connections created while it is set carry the creation time
+    /// it needs, older ones are not affected. None disable it (default)
+    pub fn set_conn_max_lifetime(&self, d: Option<Duration>) {
+        let nanos = d.map(|d| (d.as_nanos() as u64).max(1)).unwrap_or(0);
+        self.inner.conn_max_lifetime.store(nanos, Ordering::SeqCst);
+    }
+
+    pub fn conn_max_lifetime(&self) -> Option<Duration> {
+        match self.inner.conn_max_lifetime.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
+        }
+    }
+
+    pub fn max_idle_time(&self) -> Option<Duration> {
+        match self.inner.max_idle_time.load(Ordering::SeqCst) {
+            0 => None,
+            n => Some(Duration::from_nanos(n)),
         }
     }
 
+    /// no upper bound, get() never waits for capacity and the pool acts as a reuse cache:
+    /// a new connection is only created when none is idle. same as `set_max_open(UNLIMITED)`
+    pub fn set_max_open_unlimited(&self) {
+        self.set_max_open(UNLIMITED);
+    }
+
+    /// get() would not block right now: a connection is idle or there is room for a new one
+    pub(crate) fn has_capacity(&self) -> bool {
+        self.inner.flush_batched();
+        !self.inner.idle_queue.is_empty()
+            || self.inner.counters.connections() < self.inner.max_open.load(Ordering::SeqCst)
+    }
+
     pub fn set_max_open(&self, n: u64) {
         if n == 0 {
             return;
         }
-        self.max_open.store(n, Ordering::SeqCst);
-        loop {
-            if self.idle_send.len() > n as usize {
-                _ = self.idle_recv.try_recv();
-            } else {
-                break;
+        self.inner.max_open.store(n, Ordering::SeqCst);
+        self.inner.shrink_idle(n);
+    }
+
+    /// `set_max_open(n)`, then wait until the pool holds at most `n` connections.
+    /// in use connections over the limit are closed as they are returned.
+    /// return Error if the pool is still over the limit after `d`
+    pub async fn resize_and_wait(
+        &self,
+        n: u64,
+        d: Option<Duration>,
+    ) -> Result<(), PoolError<M::Error>> {
+        self.set_max_open(n);
+        let n = self.inner.max_open.load(Ordering::SeqCst);
+        let f = async {
+            loop {
+                let notified = self.inner.counters.released.notified();
+                //connections kept after a failed check may have come back to idle
+                self.inner.shrink_idle(n);
+                if self.inner.counters.connections() <= n {
+                    break;
+                }
+                notified.await;
+            }
+        };
+        match d {
+            None => f.await,
+            Some(d) => tokio::time::timeout(d, f)
+                .await
+                .map_err(|_e| PoolError::ResizeTimeout)?,
+        }
+        Ok(())
+    }
+}
+
+/// effective configuration of a pool, see `Pool::config()`
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolConfig {
+    /// max open connections, `UNLIMITED` means no limit
+    pub max_open: u64,
+    /// consecutive check failures before a kept connection is retired, 0 means no limit
+    pub max_check_failures: u64,
+    /// interval between closing idle connections, None means disabled
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub idle_decay: Option<Duration>,
+    /// see `Pool::set_max_idle_time()`, None means disabled
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub max_idle_time: Option<Duration>,
+    /// see `Pool::set_conn_max_lifetime()`, None means disabled
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub conn_max_lifetime: Option<Duration>,
+    /// how long get() waits for a returning connection before connecting, None means disabled
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub reuse_wait: Option<Duration>,
+    /// see `Pool::set_starvation_threshold()`, None means disabled
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub starvation_threshold: Option<Duration>,
+    /// see `Pool::set_fail_on_starvation()`
+    pub fail_on_starvation: bool,
+    /// see `Pool::set_saturation_alert()`, None means disabled
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub saturation_alert: Option<Duration>,
+    /// concurrent checks, see `Pool::set_max_checking()`, 0 means no limit
+    pub max_checking: u64,
+    /// see `Pool::set_check_budget_floor()`, None means disabled
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub check_budget_floor: Option<Duration>,
+    /// see `Pool::set_low_budget_action()`
+    pub low_budget_action: LowBudgetAction,
+    /// extra connections past max_open, see `Pool::set_burst()`
+    pub burst: u64,
+    /// see `Pool::set_burst()`
+    #[cfg_attr(feature = "serde", serde(with = "serde_duration"))]
+    pub burst_after: Duration,
+    /// max retries, see `Pool::set_retry_budget()`, 0 means disabled
+    pub retry_budget: u64,
+    /// see `Pool::set_retry_budget()`
+    pub retry_per_token: u64,
+    /// see `Pool::set_connect_timeout()`, None means no timeout
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub connect_timeout: Option<Duration>,
+    /// see `Pool::set_check_timeout()`, None means no timeout
+    #[cfg_attr(feature = "serde", serde(default, with = "serde_duration::option"))]
+    pub check_timeout: Option<Duration>,
+    /// see `Pool::set_check_mode()`
+    pub check_mode: CheckMode,
+    /// see `Pool::set_panic_policy()`
+    pub panic_policy: PanicPolicy,
+}
+
+impl PoolConfig {
+    /// the reason the config can't be applied, if any
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.max_open == 0 {
+            return Err("max_open must be greater than 0");
+        }
+        if let CheckMode::Sample(ratio) = self.check_mode {
+            if !(0.0..=1.0).contains(&ratio) {
+                return Err("check_mode sample ratio must be between 0 and 1");
             }
         }
+        Ok(())
+    }
+}
+
+static ACQUIRE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// how a get()/get_timeout() call ended
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum AcquireOutcome {
+    Ok,
+    /// connect or check returned an Error, or the pool is draining
+    Failed,
+    /// get_timeout() ran out of time
+    TimedOut,
+}
+
+/// a finished acquisition, see `Pool::recent_acquires()`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AcquireRecord {
+    /// when the acquisition finished
+    pub at: SystemTime,
+    /// how long it took
+    pub waited: Duration,
+    /// the connection was handed out for the first time
+    pub fresh: bool,
+    pub outcome: AcquireOutcome,
+    /// the connection handed out, None on failure
+    pub connection_id: Option<u64>,
+}
+
+static DEFAULT_MAX_OPEN: AtomicU64 = AtomicU64::new(0);
+
+/// `max_open` of pools created by `Pool::new`: the value of `set_default_max_open()`,
+/// else `FAST_POOL_MAX_OPEN` from the environment, else the number of cpus
+pub fn default_max_open() -> u64 {
+    let v = DEFAULT_MAX_OPEN.load(Ordering::SeqCst);
+    if v != 0 {
+        return v;
+    }
+    static FROM_ENV: std::sync::OnceLock<Option<u64>> = std::sync::OnceLock::new();
+    let from_env = FROM_ENV.get_or_init(|| {
+        std::env::var(MAX_OPEN_ENV)
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|v| *v > 0)
+    });
+    from_env.unwrap_or_else(|| num_cpus::get() as u64)
+}
+
+/// change the `max_open` of pools created from now on, 0 restore the default
+pub fn set_default_max_open(n: u64) {
+    DEFAULT_MAX_OPEN.store(n, Ordering::SeqCst);
+}
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+/// the id passed to `Pool::get_with`, when called from a `Manager` method running for it.
+/// use it to tag backend-side logs with the request that triggered the connection activity
+pub fn correlation_id() -> Option<String> {
+    CORRELATION_ID.try_with(|id| id.clone()).ok()
+}
+
+/// every connection stayed in use while tasks kept waiting longer than the starvation threshold,
+/// often a holder waiting on the same pool (nested get())
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StarvationEvent {
+    pub pool_id: u64,
+    pub max_open: u64,
+    pub in_use: u64,
+    /// tasks blocked in get()/get_timeout()
+    pub waiting: u64,
+    /// how long the oldest waiter has been waiting
+    pub oldest_wait: Duration,
+}
+
+/// callback receiving every `StarvationEvent`
+pub type StarvationListener = fn(&StarvationEvent);
+
+/// pool pressure alert, see `Pool::set_saturation_alert()`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SaturationEvent {
+    /// the pool has been saturated for `duration`
+    Saturated { pool_id: u64, duration: Duration },
+    /// the pool is not saturated anymore, after `duration`
+    Recovered { pool_id: u64, duration: Duration },
+}
+
+/// callback receiving every `SaturationEvent`
+pub type SaturationListener = fn(&SaturationEvent);
+
+/// a task in get()/get_timeout(), see `Pool::waiters()` and `Pool::pending_acquires()`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WaiterInfo {
+    /// unique id of the acquisition
+    pub id: u64,
+    /// the stage it is in
+    pub stage: AcquirePhase,
+    /// how long it has been in `stage`
+    pub waited: Duration,
+    /// how long since get()/get_timeout() was called
+    pub elapsed: Duration,
+}
+
+/// where a get()/get_timeout() is, see `PoolInner::enter_stage()`
+struct PendingAcquire {
+    started: Instant,
+    stage: AcquirePhase,
+    since: Instant,
+}
+
+/// true for about `ratio` of the calls, a per thread xorshift seeded like `new_pool_id`
+fn sampled(ratio: f64) -> bool {
+    thread_local! {
+        static STATE: std::cell::Cell<u64> = std::cell::Cell::new(new_pool_id() | 1);
+    }
+    let x = STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    });
+    //the top 53 bits, uniform in [0, 1)
+    ((x >> 11) as f64 / (1u64 << 53) as f64) < ratio
+}
+
+/// random enough to tell pool instances apart, without a rand dependency
+/// nanos of a background task interval, 0 (disabled) for None or zero
+fn interval_nanos(d: Option<Duration>) -> u64 {
+    d.filter(|d| !d.is_zero())
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+fn new_pool_id() -> u64 {
+    static SEQ: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(SEQ.fetch_add(1, Ordering::Relaxed));
+    if let Ok(d) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(d.as_nanos());
     }
+    hasher.finish()
 }
 
 pub struct ConnectionBox<M: Manager> {
     pub inner: Option<M::Connection>,
-    sender: Arc<Sender<M::Connection>>,
-    in_use: Arc<AtomicU64>,
-    max_open: Arc<AtomicU64>,
+    permit: Option<ConnectionPermit>,
+    health: f64,
+    usage: u64,
+    extensions: Extensions,
+    connection_extensions: Extensions,
+    pool: Arc<PoolInner<M>>,
 }
 
 impl<M: Manager> Debug for ConnectionBox<M> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ConnectionBox")
-            .field("sender", &self.sender)
+            .field("idle_queue", &self.pool.idle_queue)
             // .field("inner", &self.inner)
-            .field("in_use", &self.in_use)
-            .field("max_open", &self.max_open)
+            .field("in_use", &self.pool.counters.in_use())
+            .field("max_open", &self.pool.max_open)
             .finish()
     }
 }
 
+impl<M: Manager> ConnectionBox<M> {
+    /// id of the connection in its pool, as reported by `ConnectionEvent`
+    pub fn connection_id(&self) -> u64 {
+        self.permit.as_ref().map(|v| v.id()).unwrap_or_default()
+    }
+
+    /// health score of the connection in (0, 1], higher is better.
+    /// slow checks lower it and failed checks halve it, the pool hands out healthier connections first
+    pub fn health(&self) -> f64 {
+        self.health
+    }
+
+    /// add `n` units (bytes, operations, ...) to the usage of the connection and of the pool,
+    /// reported to the `Meter` if one is set
+    pub fn record(&mut self, n: u64) {
+        self.usage = self.usage.saturating_add(n);
+        self.pool.usage.fetch_add(n, Ordering::Relaxed);
+        if let Some(meter) = self.pool.meter.read().unwrap().as_ref() {
+            meter.record(self.connection_id(), n, self.usage);
+        }
+    }
+
+    /// units recorded on the connection since it was created, across checkouts
+    pub fn usage(&self) -> u64 {
+        self.usage
+    }
+
+    /// the connection, if it is a `T`
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T>
+    where
+        M::Connection: 'static,
+    {
+        self.inner
+            .as_ref()
+            .and_then(|v| (v as &dyn Any).downcast_ref())
+    }
+
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T>
+    where
+        M::Connection: 'static,
+    {
+        self.inner
+            .as_mut()
+            .and_then(|v| (v as &mut dyn Any).downcast_mut())
+    }
+
+    /// values attached to this checkout, dropped when the box is dropped
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// values attached to the connection itself, kept across checkouts until it is closed
+    pub fn connection_extensions(&self) -> &Extensions {
+        &self.connection_extensions
+    }
+
+    pub fn connection_extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.connection_extensions
+    }
+}
+
 impl<M: Manager> Deref for ConnectionBox<M> {
     type Target = M::Connection;
 
@@ -191,36 +2762,146 @@ impl<M: Manager> DerefMut for ConnectionBox<M> {
 
 impl<M: Manager> Drop for ConnectionBox<M> {
     fn drop(&mut self) {
-        self.in_use.fetch_sub(1, Ordering::SeqCst);
+        let Some(mut permit) = self.permit.take() else {
+            return;
+        };
+        //if the connection was taken out of the box, dropping the permit frees its slot
         if let Some(v) = self.inner.take() {
-            let max_open = self.max_open.load(Ordering::SeqCst);
-            if self.sender.len() as u64 + self.in_use.load(Ordering::SeqCst) < max_open {
-                _ = self.sender.send(v);
+            let pool = &self.pool;
+            let connections = pool.counters.connections();
+            let config = pool.config();
+            let burst = connections <= config.max_open.saturating_add(config.burst);
+            let panicking = std::thread::panicking();
+            let panic_policy = config.panic_policy;
+            //a burst connection keeps serving while tasks are still waiting
+            if pool.shut_down.load(Ordering::SeqCst) {
+                pool.close(IdleConnection::new(v, permit), CloseReason::Shutdown);
+            } else if panicking && panic_policy == PanicPolicy::Discard {
+                pool.close(IdleConnection::new(v, permit), CloseReason::Panicked);
+            } else if pool
+                .meter
+                .read()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|m| m.retire(permit.id(), self.usage))
+            {
+                pool.close(IdleConnection::new(v, permit), CloseReason::Retired);
+            } else if pool.expired(&permit) {
+                pool.close(IdleConnection::new(v, permit), CloseReason::Expired);
+            } else if connections > config.max_open
+                && !(burst && pool.waiting.load(Ordering::SeqCst) > 0)
+            {
+                let reason = if burst {
+                    CloseReason::Burst
+                } else {
+                    CloseReason::Resized
+                };
+                pool.close(IdleConnection::new(v, permit), reason);
+            } else {
+                permit.transition(ConnectionState::Idle);
+                let mut idle = IdleConnection::new(v, permit);
+                idle.health = self.health;
+                idle.usage = self.usage;
+                idle.extensions = std::mem::take(&mut self.connection_extensions);
+                idle.must_check = panicking && panic_policy == PanicPolicy::Check;
+                pool.recycle(idle);
             }
         }
     }
 }
 
+/// per second rates, see `Pool::rates()`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rates {
+    /// finished get()/get_timeout() calls, whatever their outcome
+    pub acquires: f64,
+    /// connections created
+    pub connects: f64,
+    /// get_timeout() calls that ran out of time
+    pub timeouts: f64,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct State {
-    /// max open limit
+    /// id of the pool instance
+    pub id: u64,
+    /// max open limit, `UNLIMITED` means no limit
     pub max_open: u64,
-    ///connections = in_use number + idle number
+    ///connections = in_use number + idle number + connections being created
     pub connections: u64,
     /// user use connection number
     pub in_use: u64,
     /// idle connection
     pub idle: u64,
-    /// wait get connections number
-    pub waits: u64,
+    /// get()/get_timeout() calls not finished yet
+    pub acquires_in_flight: u64,
+    /// get()/get_timeout() calls blocked waiting for a connection to be returned
+    pub waiting: u64,
+    /// get()/get_timeout() calls waiting for a check slot, see `Pool::set_max_checking()`
+    pub waiting_check: u64,
+    /// get()/get_timeout() calls creating a connection with `Manager::connect`
+    pub connecting: u64,
+    /// get()/get_timeout() calls validating a connection with `Manager::check`
+    pub checking: u64,
+    /// moving average of how long successful get()/get_timeout() calls took, in microseconds
+    pub avg_wait_us: u64,
+    /// successful get()/get_timeout() calls since the pool was built, counted in `avg_wait_us`
+    pub acquires: u64,
+    /// connections created since the pool was built
+    pub created: u64,
+    /// connections closed since the pool was built, for any `CloseReason`
+    pub closed: u64,
+    /// `closed` by `CloseReason`
+    pub closed_by: CloseCounts,
+    /// live `Pool` clones, see `Pool::handles()`
+    pub handles: u64,
+}
+
+impl State {
+    /// what happened between the `earlier` snapshot and this one
+    pub fn diff(&self, earlier: &State) -> StateDelta {
+        StateDelta {
+            created: self.created.saturating_sub(earlier.created),
+            closed: self.closed.saturating_sub(earlier.closed),
+            closed_by: self.closed_by.diff(&earlier.closed_by),
+            acquires: self.acquires.saturating_sub(earlier.acquires),
+        }
+    }
+}
+
+/// difference between two `State` snapshots, see `State::diff()`
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct StateDelta {
+    /// connections created
+    pub created: u64,
+    /// connections closed
+    pub closed: u64,
+    /// `closed` by `CloseReason`
+    pub closed_by: CloseCounts,
+    /// successful get()/get_timeout() calls
+    pub acquires: u64,
 }
 
 impl Display for State {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{{ max_open: {}, connections: {}, in_use: {}, idle: {}, waits: {} }}",
-            self.max_open, self.connections, self.in_use, self.idle, self.waits
+            "{{ id: {}, max_open: {}, connections: {}, in_use: {}, idle: {}, acquires_in_flight: {}, waiting: {}, waiting_check: {}, connecting: {}, checking: {}, avg_wait_us: {}, acquires: {}, created: {}, closed: {}, handles: {} }}",
+            self.id,
+            self.max_open,
+            self.connections,
+            self.in_use,
+            self.idle,
+            self.acquires_in_flight,
+            self.waiting,
+            self.waiting_check,
+            self.connecting,
+            self.checking,
+            self.avg_wait_us,
+            self.acquires,
+            self.created,
+            self.closed,
+            self.handles
         )
     }
 }
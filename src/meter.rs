@@ -0,0 +1,23 @@
+//! usage accounting of connections, see `Pool::set_meter()`
+
+/// consulted with the usage applications report by `ConnectionBox::record()`
+/// (bytes, operations or any custom unit), to build usage based policies
+pub trait Meter: Send + Sync {
+    /// `n` units were recorded on the connection `connection_id`, its total is now `usage`
+    fn record(&self, connection_id: u64, n: u64, usage: u64) {
+        let _ = (connection_id, n, usage);
+    }
+
+    /// close the connection with `CloseReason::Retired` instead of returning it idle
+    fn retire(&self, connection_id: u64, usage: u64) -> bool;
+}
+
+/// a `Meter` retiring connections once their usage reaches the limit, e.g. bytes transferred
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct UsageLimit(pub u64);
+
+impl Meter for UsageLimit {
+    fn retire(&self, _connection_id: u64, usage: u64) -> bool {
+        usage >= self.0
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{ConnectionBox, Manager, Pool, PoolError};
+use std::cell::Cell;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// partition slot of the current thread, assigned round-robin on first use
+    static SLOT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+pub(crate) fn thread_slot() -> usize {
+    SLOT.with(|slot| match slot.get() {
+        Some(v) => v,
+        None => {
+            let v = NEXT_SLOT.fetch_add(1, Ordering::Relaxed);
+            slot.set(Some(v));
+            v
+        }
+    })
+}
+
+/// pool split in one sub-pool per worker thread, each with a slice of max_open.
+/// a thread acquires from its own partition, and steals from another one only
+/// when its own has no idle connection and no room left, so hot connections stay on one core
+pub struct PartitionedPool<M: Manager> {
+    partitions: Vec<Pool<M>>,
+}
+
+impl<M: Manager + Clone> PartitionedPool<M> {
+    /// `partitions` sub-pools sharing `max_open`, every partition get at least 1 connection
+    pub fn new(m: M, partitions: usize, max_open: u64) -> Self
+    where
+        M::Connection: Unpin,
+    {
+        let n = (partitions.max(1) as u64).min(max_open.max(1));
+        let partitions = (0..n)
+            .map(|i| {
+                let pool = Pool::new(m.clone());
+                //spread the remainder over the first partitions
+                pool.set_max_open(max_open.max(1) / n + u64::from(i < max_open.max(1) % n));
+                pool
+            })
+            .collect();
+        Self { partitions }
+    }
+}
+
+impl<M: Manager> PartitionedPool<M> {
+    pub fn partitions(&self) -> &[Pool<M>] {
+        &self.partitions
+    }
+
+    /// the partition of the current thread
+    pub fn local(&self) -> &Pool<M> {
+        &self.partitions[thread_slot() % self.partitions.len()]
+    }
+
+    pub async fn get(&self) -> Result<ConnectionBox<M>, PoolError<M::Error>> {
+        self.get_timeout(None).await
+    }
+
+    pub async fn get_timeout(
+        &self,
+        d: Option<Duration>,
+    ) -> Result<ConnectionBox<M>, PoolError<M::Error>> {
+        let start = thread_slot();
+        let local = &self.partitions[start % self.partitions.len()];
+        if !local.has_capacity() {
+            let len = self.partitions.len();
+            for i in 1..len {
+                let other = &self.partitions[(start + i) % len];
+                if other.has_capacity() {
+                    return other.get_timeout(d).await;
+                }
+            }
+        }
+        local.get_timeout(d).await
+    }
+}
+
+impl<M: Manager> Debug for PartitionedPool<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PartitionedPool")
+            .field("partitions", &self.partitions)
+            .finish()
+    }
+}
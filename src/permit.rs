@@ -0,0 +1,419 @@
+use crate::rate::RollingRate;
+use crate::CachePadded;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::Notify;
+
+/// why the pool closed a connection
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CloseReason {
+    /// `Manager::check` failed, or the connection reached `max_check_failures`
+    CheckFailed,
+    /// the connection no longer fit after `set_max_open` shrank the pool
+    Resized,
+    /// surplus idle connection shed by `set_idle_decay`
+    IdleDecay,
+    /// idle longer than `set_max_idle_time`
+    IdleTimeout,
+    /// older than `set_conn_max_lifetime`
+    Expired,
+    /// burst connection returned once no task was waiting anymore, see `Pool::set_burst`
+    Burst,
+    /// the pool was shut down, see `Pool::shutdown`
+    Shutdown,
+    /// returned while its thread was panicking, see `Pool::set_panic_policy`
+    Panicked,
+    /// the `Meter` retired it for its usage, see `Pool::set_meter`
+    Retired,
+    /// the connection left the pool without the pool closing it:
+    /// taken out of `ConnectionBox::inner`, or the get() was cancelled while checking it
+    Discarded,
+}
+
+impl CloseReason {
+    pub const ALL: [CloseReason; 10] = [
+        CloseReason::CheckFailed,
+        CloseReason::Resized,
+        CloseReason::IdleDecay,
+        CloseReason::IdleTimeout,
+        CloseReason::Expired,
+        CloseReason::Burst,
+        CloseReason::Shutdown,
+        CloseReason::Panicked,
+        CloseReason::Retired,
+        CloseReason::Discarded,
+    ];
+}
+
+/// connections closed by `CloseReason`, see `Pool::close_counts()` and `State::closed_by`
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct CloseCounts {
+    pub check_failed: u64,
+    pub resized: u64,
+    pub idle_decay: u64,
+    pub idle_timeout: u64,
+    pub expired: u64,
+    pub burst: u64,
+    pub shutdown: u64,
+    pub panicked: u64,
+    pub retired: u64,
+    pub discarded: u64,
+}
+
+impl CloseCounts {
+    pub fn get(&self, reason: CloseReason) -> u64 {
+        match reason {
+            CloseReason::CheckFailed => self.check_failed,
+            CloseReason::Resized => self.resized,
+            CloseReason::IdleDecay => self.idle_decay,
+            CloseReason::IdleTimeout => self.idle_timeout,
+            CloseReason::Expired => self.expired,
+            CloseReason::Burst => self.burst,
+            CloseReason::Shutdown => self.shutdown,
+            CloseReason::Panicked => self.panicked,
+            CloseReason::Retired => self.retired,
+            CloseReason::Discarded => self.discarded,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        CloseReason::ALL.iter().map(|v| self.get(*v)).sum()
+    }
+
+    /// connections closed between the `earlier` counts and these
+    pub fn diff(&self, earlier: &CloseCounts) -> CloseCounts {
+        CloseCounts {
+            check_failed: self.check_failed.saturating_sub(earlier.check_failed),
+            resized: self.resized.saturating_sub(earlier.resized),
+            idle_decay: self.idle_decay.saturating_sub(earlier.idle_decay),
+            idle_timeout: self.idle_timeout.saturating_sub(earlier.idle_timeout),
+            expired: self.expired.saturating_sub(earlier.expired),
+            burst: self.burst.saturating_sub(earlier.burst),
+            shutdown: self.shutdown.saturating_sub(earlier.shutdown),
+            panicked: self.panicked.saturating_sub(earlier.panicked),
+            retired: self.retired.saturating_sub(earlier.retired),
+            discarded: self.discarded.saturating_sub(earlier.discarded),
+        }
+    }
+}
+
+/// lifecycle of a connection inside a pool
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ConnectionState {
+    /// `Manager::connect` returned it
+    Created,
+    /// waiting in the idle queue
+    Idle,
+    /// taken from the idle queue, `Manager::check` is running
+    Checking,
+    /// handed out to the user in a `ConnectionBox`
+    InUse,
+    /// gone from the pool
+    Closed(CloseReason),
+}
+
+impl ConnectionState {
+    fn is_in_use(&self) -> bool {
+        matches!(self, ConnectionState::Checking | ConnectionState::InUse)
+    }
+}
+
+/// a connection changed `ConnectionState`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ConnectionEvent {
+    /// `Pool::id()` of the pool owning the connection
+    pub pool_id: u64,
+    /// id of the connection, unique in its pool
+    pub connection_id: u64,
+    /// None when the connection just entered the pool
+    pub from: Option<ConnectionState>,
+    pub to: ConnectionState,
+}
+
+/// callback receiving every `ConnectionEvent`
+pub type ConnectionListener = fn(&ConnectionEvent);
+
+/// number of closed connections by `CloseReason`
+#[derive(Debug, Default)]
+pub(crate) struct CloseCounters {
+    check_failed: AtomicU64,
+    resized: AtomicU64,
+    idle_decay: AtomicU64,
+    idle_timeout: AtomicU64,
+    expired: AtomicU64,
+    burst: AtomicU64,
+    shutdown: AtomicU64,
+    panicked: AtomicU64,
+    retired: AtomicU64,
+    discarded: AtomicU64,
+}
+
+impl CloseCounters {
+    pub(crate) fn get(&self, reason: CloseReason) -> &AtomicU64 {
+        match reason {
+            CloseReason::CheckFailed => &self.check_failed,
+            CloseReason::Resized => &self.resized,
+            CloseReason::IdleDecay => &self.idle_decay,
+            CloseReason::IdleTimeout => &self.idle_timeout,
+            CloseReason::Expired => &self.expired,
+            CloseReason::Burst => &self.burst,
+            CloseReason::Shutdown => &self.shutdown,
+            CloseReason::Panicked => &self.panicked,
+            CloseReason::Retired => &self.retired,
+            CloseReason::Discarded => &self.discarded,
+        }
+    }
+
+    pub(crate) fn total(&self) -> u64 {
+        CloseReason::ALL
+            .iter()
+            .map(|v| self.get(*v).load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub(crate) fn snapshot(&self) -> CloseCounts {
+        let get = |reason| self.get(reason).load(Ordering::Relaxed);
+        CloseCounts {
+            check_failed: get(CloseReason::CheckFailed),
+            resized: get(CloseReason::Resized),
+            idle_decay: get(CloseReason::IdleDecay),
+            idle_timeout: get(CloseReason::IdleTimeout),
+            expired: get(CloseReason::Expired),
+            burst: get(CloseReason::Burst),
+            shutdown: get(CloseReason::Shutdown),
+            panicked: get(CloseReason::Panicked),
+            retired: get(CloseReason::Retired),
+            discarded: get(CloseReason::Discarded),
+        }
+    }
+}
+
+/// bits of each count packed in `Counters::counts`
+const COUNT_BITS: u32 = 21;
+/// most connections a pool can hold, even with an unlimited max_open
+pub(crate) const MAX_CONNECTIONS: u64 = (1 << COUNT_BITS) - 1;
+const CONNECTION: u64 = 1;
+const IN_USE: u64 = 1 << COUNT_BITS;
+const IDLE: u64 = 1 << (2 * COUNT_BITS);
+
+/// connection counters, shared by the pool and every `ConnectionPermit`.
+/// kept out of `PoolInner`, idle connections hold permits and would otherwise keep the pool alive
+pub(crate) struct Counters {
+    pub(crate) pool_id: u64,
+    /// open connections (including the ones being connected), connections checking or
+    /// handed out, and connections in the idle queue, packed in one word so a single load
+    /// reads them together. a connection is counted in use or idle only while it is counted
+    /// open, so in_use + idle <= connections in every snapshot
+    counts: CachePadded<AtomicU64>,
+    pub(crate) closed: CloseCounters,
+    /// connections ever created
+    pub(crate) created: AtomicU64,
+    pub(crate) connects: RollingRate,
+    /// notified once in_use drops to 0, see `drain()`
+    pub(crate) drained: Notify,
+    /// notified every time a connection slot is freed, see `resize_and_wait()`
+    pub(crate) released: Notify,
+    next_id: AtomicU64,
+    has_listener: AtomicBool,
+    listener: RwLock<Option<ConnectionListener>>,
+}
+
+impl Counters {
+    pub(crate) fn new(pool_id: u64) -> Self {
+        Self {
+            pool_id,
+            counts: CachePadded(AtomicU64::new(0)),
+            closed: CloseCounters::default(),
+            created: AtomicU64::new(0),
+            connects: RollingRate::new(),
+            drained: Notify::new(),
+            released: Notify::new(),
+            next_id: AtomicU64::new(0),
+            has_listener: AtomicBool::new(false),
+            listener: RwLock::new(None),
+        }
+    }
+
+    /// (connections, in_use, idle)
+    pub(crate) fn snapshot(&self) -> (u64, u64, u64) {
+        unpack(self.counts.load(Ordering::SeqCst))
+    }
+
+    pub(crate) fn connections(&self) -> u64 {
+        self.snapshot().0
+    }
+
+    pub(crate) fn in_use(&self) -> u64 {
+        self.snapshot().1
+    }
+
+    /// a connection is about to enter the idle queue
+    pub(crate) fn enter_idle(&self) {
+        self.counts.fetch_add(IDLE, Ordering::SeqCst);
+    }
+
+    /// a connection left the idle queue
+    pub(crate) fn leave_idle(&self) {
+        let counts = self.counts.fetch_sub(IDLE, Ordering::SeqCst);
+        strict!(unpack(counts).2 != 0, "idle counter underflow");
+    }
+
+    pub(crate) fn set_listener(&self, listener: Option<ConnectionListener>) {
+        *self.listener.write().unwrap() = listener;
+        self.has_listener
+            .store(listener.is_some(), Ordering::SeqCst);
+    }
+
+    fn emit(&self, event: ConnectionEvent) {
+        if !self.has_listener.load(Ordering::Relaxed) {
+            return;
+        }
+        let listener = *self.listener.read().unwrap();
+        if let Some(listener) = listener {
+            listener(&event);
+        }
+    }
+}
+
+/// one unit of pool capacity, owned by exactly one connection or connect in progress.
+/// it is the connection's state machine: `transition` and Drop are the only places
+/// `connections`/`in_use`/close counters change
+pub(crate) struct ConnectionPermit {
+    counters: Arc<Counters>,
+    id: u64,
+    /// None until the connection is created
+    state: Option<ConnectionState>,
+    close_reason: CloseReason,
+    /// times the connection was handed out
+    checkouts: u64,
+    /// when the connection was created, only stamped while the pool has a max lifetime
+    created_at: Option<Instant>,
+}
+
+impl ConnectionPermit {
+    /// a single CAS, so concurrent acquirers can never overshoot max_open
+    pub(crate) fn reserve(counters: &Arc<Counters>, max_open: u64) -> Option<Self> {
+        let max_open = max_open.min(MAX_CONNECTIONS);
+        counters
+            .counts
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                if unpack(n).0 < max_open {
+                    Some(n + CONNECTION)
+                } else {
+                    None
+                }
+            })
+            .ok()?;
+        Some(Self {
+            counters: counters.clone(),
+            id: counters.next_id.fetch_add(1, Ordering::Relaxed),
+            state: None,
+            close_reason: CloseReason::Discarded,
+            checkouts: 0,
+            created_at: None,
+        })
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn pool_id(&self) -> u64 {
+        self.counters.pool_id
+    }
+
+    pub(crate) fn state(&self) -> Option<ConnectionState> {
+        self.state
+    }
+
+    pub(crate) fn checkouts(&self) -> u64 {
+        self.checkouts
+    }
+
+    pub(crate) fn stamp_created(&mut self) {
+        self.created_at = Some(Instant::now());
+    }
+
+    pub(crate) fn created_at(&self) -> Option<Instant> {
+        self.created_at
+    }
+
+    pub(crate) fn transition(&mut self, to: ConnectionState) {
+        let from = self.state;
+        if from == Some(to) {
+            return;
+        }
+        strict!(
+            !matches!(from, Some(ConnectionState::Closed(_))),
+            "connection {} used after it was closed",
+            self.id
+        );
+        self.state = Some(to);
+        let was_in_use = from.map(|v| v.is_in_use()).unwrap_or(false);
+        if !was_in_use && to.is_in_use() {
+            self.counters.counts.fetch_add(IN_USE, Ordering::SeqCst);
+        } else if was_in_use && !to.is_in_use() {
+            let (_, in_use, _) = unpack(self.counters.counts.fetch_sub(IN_USE, Ordering::SeqCst));
+            strict!(in_use != 0, "in_use counter underflow");
+            if in_use == 1 {
+                self.counters.drained.notify_waiters();
+            }
+        }
+        if to == ConnectionState::InUse {
+            self.checkouts += 1;
+        }
+        if to == ConnectionState::Created {
+            self.counters.created.fetch_add(1, Ordering::SeqCst);
+            self.counters.connects.record();
+        }
+        if let ConnectionState::Closed(reason) = to {
+            self.counters
+                .closed
+                .get(reason)
+                .fetch_add(1, Ordering::SeqCst);
+        }
+        self.counters.emit(ConnectionEvent {
+            pool_id: self.counters.pool_id,
+            connection_id: self.id,
+            from,
+            to,
+        });
+    }
+
+    /// close the connection for `reason` when the permit is dropped
+    pub(crate) fn close(mut self, reason: CloseReason) {
+        self.close_reason = reason;
+    }
+
+    /// hand the connection over to the pool owning `to`, without closing it here
+    pub(crate) fn transfer(mut self, mut to: ConnectionPermit) -> ConnectionPermit {
+        to.checkouts = self.checkouts;
+        to.created_at = self.created_at;
+        if let Some(state) = self.state.take() {
+            to.transition(state);
+        }
+        to
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        if self.state.is_some() {
+            self.transition(ConnectionState::Closed(self.close_reason));
+        }
+        let (connections, _, _) =
+            unpack(self.counters.counts.fetch_sub(CONNECTION, Ordering::SeqCst));
+        strict!(connections != 0, "connections counter underflow");
+        self.counters.released.notify_waiters();
+    }
+}
+
+/// (connections, in_use, idle) of a `Counters::counts` word
+fn unpack(counts: u64) -> (u64, u64, u64) {
+    (
+        counts & MAX_CONNECTIONS,
+        (counts / IN_USE) & MAX_CONNECTIONS,
+        (counts / IDLE) & MAX_CONNECTIONS,
+    )
+}
@@ -0,0 +1,107 @@
+use crate::{Manager, Pool};
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+
+/// future returned by `DynManager`. it is `Send` when feature `send` is enabled
+#[cfg(feature = "send")]
+pub type ManagerFuture<'a, T> = futures_core::future::BoxFuture<'a, T>;
+
+/// future returned by `DynManager`. it is `Send` when feature `send` is enabled
+#[cfg(not(feature = "send"))]
+pub type ManagerFuture<'a, T> = futures_core::future::LocalBoxFuture<'a, T>;
+
+/// object safe version of `Manager`, implemented for every `Manager`
+pub trait DynManager<C, E> {
+    fn connect(&self) -> ManagerFuture<'_, Result<C, E>>;
+    fn check<'a>(&'a self, conn: &'a mut C) -> ManagerFuture<'a, Result<(), E>>;
+    fn disconnect(&self, conn: C) -> ManagerFuture<'_, ()>;
+    /// the concrete manager, for downcasting
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<M: Manager + 'static> DynManager<M::Connection, M::Error> for M {
+    fn connect(&self) -> ManagerFuture<'_, Result<M::Connection, M::Error>> {
+        Box::pin(Manager::connect(self))
+    }
+
+    fn check<'a>(&'a self, conn: &'a mut M::Connection) -> ManagerFuture<'a, Result<(), M::Error>> {
+        Box::pin(Manager::check(self, conn))
+    }
+
+    fn disconnect(&self, conn: M::Connection) -> ManagerFuture<'_, ()> {
+        Box::pin(Manager::disconnect(self, conn))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// BoxManager erase the Manager type, so the Manager can be chosen at runtime.
+/// ```rust
+/// use fast_pool::plugin::BoxManager;
+/// use fast_pool::{Manager, Pool};
+///
+/// pub struct StringManager {}
+///
+/// impl Manager for StringManager {
+///     type Connection = String;
+///     type Error = String;
+///
+///     async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+///         Ok(String::new())
+///     }
+///
+///     async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// let p: Pool<BoxManager<String, String>> = Pool::new(BoxManager::new(StringManager {}));
+/// ```
+pub struct BoxManager<C, E> {
+    inner: Box<dyn DynManager<C, E> + Send + Sync>,
+}
+
+/// a pool over a `BoxManager`, the type of any manager chain once erased
+pub type BoxPool<C, E> = Pool<BoxManager<C, E>>;
+
+impl<C, E> BoxManager<C, E> {
+    pub fn new<M>(m: M) -> Self
+    where
+        M: Manager<Connection = C, Error = E> + Send + Sync + 'static,
+    {
+        Self { inner: Box::new(m) }
+    }
+
+    /// the erased manager, if it is a `T`
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.inner.as_any().downcast_ref()
+    }
+}
+
+impl<C, E> Debug for BoxManager<C, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxManager").finish()
+    }
+}
+
+impl<C, E> Manager for BoxManager<C, E>
+where
+    C: Send,
+{
+    type Connection = C;
+    type Error = E;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        self.inner.connect().await
+    }
+
+    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        self.inner.check(conn).await
+    }
+
+    async fn disconnect(&self, conn: Self::Connection) {
+        self.inner.disconnect(conn).await
+    }
+}
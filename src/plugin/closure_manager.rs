@@ -0,0 +1,70 @@
+use crate::Manager;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::marker::PhantomData;
+
+/// ClosureManager build a Manager from an async connect closure and an async check closure.
+/// the check future can't borrow the connection, take what it needs first,
+/// e.g. a clone of a cheaply cloned client to ping with.
+/// ```rust
+/// use fast_pool::plugin::ClosureManager;
+/// use fast_pool::Pool;
+///
+/// let p = Pool::new(ClosureManager::new(
+///     || async { Ok::<_, String>(String::new()) },
+///     |conn: &mut String| {
+///         let client = conn.clone();
+///         async move {
+///             //client.ping().await
+///             if client.is_empty() { Ok(()) } else { Err(client) }
+///         }
+///     },
+/// ));
+/// ```
+pub struct ClosureManager<C, E, F, G> {
+    connect: F,
+    check: G,
+    phantom: PhantomData<fn() -> (C, E)>,
+}
+
+impl<C, E, F, Fut, G, GFut> ClosureManager<C, E, F, G>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<C, E>>,
+    G: Fn(&mut C) -> GFut,
+    GFut: Future<Output = Result<(), E>>,
+{
+    pub fn new(connect: F, check: G) -> Self {
+        Self {
+            connect,
+            check,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, E, F, G> Debug for ClosureManager<C, E, F, G> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClosureManager").finish()
+    }
+}
+
+impl<C, E, F, Fut, G, GFut> Manager for ClosureManager<C, E, F, G>
+where
+    C: Send,
+    F: Fn() -> Fut + Sync,
+    Fut: Future<Output = Result<C, E>> + Send,
+    G: Fn(&mut C) -> GFut + Sync,
+    GFut: Future<Output = Result<(), E>> + Send,
+{
+    type Connection = C;
+    type Error = E;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        (self.connect)().await
+    }
+
+    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        (self.check)(conn).await
+    }
+}
@@ -0,0 +1,94 @@
+use crate::Manager;
+use hyper::body::Body;
+use hyper::client::conn::http1::{handshake, SendRequest};
+use hyper::rt::{Read, Write};
+use hyper::Request;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+/// HyperManager pool http1 connections of a hyper client, enabled by feature `hyper`.
+/// `connect` open the transport (for tokio streams wrap them in `hyper_util::rt::TokioIo`),
+/// the handshake is done here and the connection task is spawned on tokio.
+/// check fails once the connection is closed, and with `health_check` also when
+/// a request to the path does not return a success status
+pub struct HyperManager<F, B, E> {
+    connect: F,
+    /// Host header and path of the health check request
+    health_check: Option<(String, String)>,
+    phantom: PhantomData<fn() -> (B, E)>,
+}
+
+impl<F, Fut, IO, B, E> HyperManager<F, B, E>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<IO, E>>,
+    IO: Read + Write + Unpin,
+{
+    pub fn new(connect: F) -> Self {
+        Self {
+            connect,
+            health_check: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// GET `path` on every check, e.g. "/health". `host` is sent as the Host header
+    /// HTTP/1.1 requires, e.g. "db.internal:8080": hyper's http1 connection does not add it
+    pub fn health_check(mut self, host: &str, path: &str) -> Self {
+        self.health_check = Some((host.to_string(), path.to_string()));
+        self
+    }
+}
+
+impl<F, B, E> Debug for HyperManager<F, B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HyperManager")
+            .field("health_check", &self.health_check)
+            .finish()
+    }
+}
+
+impl<F, Fut, IO, B, E> Manager for HyperManager<F, B, E>
+where
+    F: Fn() -> Fut + Sync,
+    Fut: Future<Output = Result<IO, E>> + Send,
+    IO: Read + Write + Unpin + Send + 'static,
+    B: Body + Default + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    E: for<'a> From<&'a str> + From<hyper::Error>,
+{
+    type Connection = SendRequest<B>;
+    type Error = E;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let io = (self.connect)().await?;
+        let (sender, conn) = handshake(io).await?;
+        tokio::spawn(conn);
+        Ok(sender)
+    }
+
+    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        conn.ready().await?;
+        let Some((host, path)) = &self.health_check else {
+            return Ok(());
+        };
+        let req = Request::get(path.as_str())
+            .header(hyper::header::HOST, host.as_str())
+            .body(B::default())
+            .map_err(|e| E::from(e.to_string().as_str()))?;
+        let mut res = conn.send_request(req).await?;
+        //read the body out, so the connection can be reused
+        let body = res.body_mut();
+        while let Some(frame) = std::future::poll_fn(|cx| Pin::new(&mut *body).poll_frame(cx)).await
+        {
+            frame?;
+        }
+        if !res.status().is_success() {
+            return Err(E::from(res.status().as_str()));
+        }
+        Ok(())
+    }
+}
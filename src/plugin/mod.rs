@@ -0,0 +1,15 @@
+mod box_manager;
+mod closure_manager;
+#[cfg(feature = "hyper")]
+mod hyper_manager;
+mod sync_manager;
+#[cfg(feature = "tonic")]
+mod tonic_manager;
+
+pub use box_manager::{BoxManager, BoxPool, DynManager, ManagerFuture};
+pub use closure_manager::ClosureManager;
+#[cfg(feature = "hyper")]
+pub use hyper_manager::HyperManager;
+pub use sync_manager::{BlockingConnection, BlockingError, SyncManager, SyncManagerBridge};
+#[cfg(feature = "tonic")]
+pub use tonic_manager::TonicManager;
@@ -0,0 +1,175 @@
+use crate::Manager;
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// blocking version of `Manager`, like `r2d2::ManageConnection`
+pub trait SyncManager: Send + Sync + 'static {
+    type Connection: Send + 'static;
+    type Error: Send + 'static;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error>;
+    fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error>;
+}
+
+/// SyncManagerBridge run a `SyncManager` on tokio's blocking thread pool, so blocking drivers can be pooled.
+/// ```rust
+/// use fast_pool::plugin::{SyncManager, SyncManagerBridge};
+/// use fast_pool::Pool;
+///
+/// pub struct BlockingManager {}
+///
+/// impl SyncManager for BlockingManager {
+///     type Connection = String;
+///     type Error = String;
+///
+///     fn connect(&self) -> Result<Self::Connection, Self::Error> {
+///         Ok(String::new())
+///     }
+///
+///     fn is_valid(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// let p = Pool::new(SyncManagerBridge::new(BlockingManager {}).max_blocking(4));
+/// ```
+pub struct SyncManagerBridge<T: SyncManager> {
+    inner: Arc<T>,
+    blocking: Option<Arc<Semaphore>>,
+}
+
+impl<T: SyncManager> SyncManagerBridge<T> {
+    pub fn new(m: T) -> Self {
+        Self {
+            inner: Arc::new(m),
+            blocking: None,
+        }
+    }
+
+    /// run at most `n` blocking calls of this manager at once, the others wait their turn
+    pub fn max_blocking(mut self, n: usize) -> Self {
+        self.blocking = Some(Arc::new(Semaphore::new(n.max(1))));
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    async fn run<R, F>(&self, f: F) -> Result<R, BlockingError<T::Error>>
+    where
+        R: Send + 'static,
+        F: FnOnce(&T) -> R + Send + 'static,
+    {
+        let _permit = match &self.blocking {
+            None => None,
+            Some(v) => Some(
+                v.acquire()
+                    .await
+                    .map_err(|e| BlockingError::Blocking(e.to_string()))?,
+            ),
+        };
+        let m = self.inner.clone();
+        tokio::task::spawn_blocking(move || f(&m))
+            .await
+            .map_err(|e| BlockingError::Blocking(e.to_string()))
+    }
+}
+
+impl<T: SyncManager> Debug for SyncManagerBridge<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncManagerBridge").finish()
+    }
+}
+
+impl<T: SyncManager> Manager for SyncManagerBridge<T> {
+    type Connection = BlockingConnection<T::Connection>;
+    type Error = BlockingError<T::Error>;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let conn = self
+            .run(|m| m.connect())
+            .await?
+            .map_err(BlockingError::Manager)?;
+        Ok(BlockingConnection { inner: Some(conn) })
+    }
+
+    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        //the connection moves to the blocking thread and back
+        let Some(mut c) = conn.inner.take() else {
+            return Err(BlockingError::ConnectionLost);
+        };
+        let (c, result) = self
+            .run(move |m| {
+                let result = m.is_valid(&mut c);
+                (c, result)
+            })
+            .await?;
+        conn.inner = Some(c);
+        result.map_err(BlockingError::Manager)
+    }
+}
+
+/// Error of a `SyncManagerBridge`: the `SyncManager` Error, or the blocking call failing to run
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BlockingError<E> {
+    /// returned by the `SyncManager`
+    Manager(E),
+    /// the blocking call could not run to the end: it panicked or the runtime shut down
+    Blocking(String),
+    /// a cancelled check left the connection on the blocking thread
+    ConnectionLost,
+}
+
+impl<E: Display> Display for BlockingError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockingError::Manager(e) => e.fmt(f),
+            BlockingError::Blocking(e) => write!(f, "blocking call failed: {}", e),
+            BlockingError::ConnectionLost => f.write_str("connection lost in a cancelled check"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for BlockingError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BlockingError::Manager(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// connection of a `SyncManagerBridge`, deref to the `SyncManager` connection
+pub struct BlockingConnection<C> {
+    inner: Option<C>,
+}
+
+impl<C> BlockingConnection<C> {
+    /// the `SyncManager` connection, None if a cancelled check left it on the blocking thread
+    pub fn into_inner(self) -> Option<C> {
+        self.inner
+    }
+}
+
+impl<C: Debug> Debug for BlockingConnection<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<C> Deref for BlockingConnection<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref().unwrap()
+    }
+}
+
+impl<C> DerefMut for BlockingConnection<C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut().unwrap()
+    }
+}
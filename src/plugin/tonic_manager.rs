@@ -0,0 +1,174 @@
+use crate::Manager;
+use bytes::{Buf, BufMut};
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use tonic::client::Grpc;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+
+const HEALTH_CHECK: &str = "/grpc.health.v1.Health/Check";
+/// `grpc.health.v1.HealthCheckResponse.ServingStatus.SERVING`
+const SERVING: u64 = 1;
+
+/// TonicManager pool tonic `Channel`s, enabled by feature `tonic`.
+/// the `Endpoint` carries the connection settings (timeouts, keep alive, and TLS once
+/// a tonic tls feature is enabled). check fails once the channel can't send anymore,
+/// and with `health_check` also when `grpc.health.v1.Health/Check` does not answer SERVING
+pub struct TonicManager<E> {
+    endpoint: Endpoint,
+    health_check: Option<String>,
+    phantom: PhantomData<fn() -> E>,
+}
+
+impl<E> TonicManager<E> {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self {
+            endpoint,
+            health_check: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// call the standard health check for `service` on every check, "" means the whole server
+    pub fn health_check(mut self, service: &str) -> Self {
+        self.health_check = Some(service.to_string());
+        self
+    }
+
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+}
+
+impl<E> Debug for TonicManager<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TonicManager")
+            .field("endpoint", &self.endpoint.uri())
+            .field("health_check", &self.health_check)
+            .finish()
+    }
+}
+
+impl<E> Manager for TonicManager<E>
+where
+    E: for<'a> From<&'a str> + From<tonic::transport::Error> + From<Status>,
+{
+    type Connection = Channel;
+    type Error = E;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(self.endpoint.connect().await?)
+    }
+
+    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        let mut grpc = Grpc::new(conn.clone());
+        grpc.ready().await?;
+        let Some(service) = &self.health_check else {
+            return Ok(());
+        };
+        let status = grpc
+            .unary(
+                Request::new(service.clone()),
+                PathAndQuery::from_static(HEALTH_CHECK),
+                HealthCodec,
+            )
+            .await?
+            .into_inner();
+        if status != SERVING {
+            return Err(E::from("NOT_SERVING"));
+        }
+        Ok(())
+    }
+}
+
+/// the health check messages encoded by hand, to spare a protobuf dependency:
+/// HealthCheckRequest { string service = 1 }, HealthCheckResponse { ServingStatus status = 1 }
+#[derive(Debug, Clone, Copy, Default)]
+struct HealthCodec;
+
+impl Codec for HealthCodec {
+    type Encode = String;
+    type Decode = u64;
+    type Encoder = HealthCodec;
+    type Decoder = HealthCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        HealthCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        HealthCodec
+    }
+}
+
+impl Encoder for HealthCodec {
+    type Item = String;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        if !item.is_empty() {
+            //field 1, length delimited
+            dst.put_u8(0x0a);
+            put_varint(dst, item.len() as u64);
+            dst.put_slice(item.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl Decoder for HealthCodec {
+    type Item = u64;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let invalid = || Status::internal("invalid HealthCheckResponse");
+        let mut status = 0;
+        while src.has_remaining() {
+            let key = get_varint(src).ok_or_else(invalid)?;
+            match key & 0x7 {
+                0 => {
+                    let v = get_varint(src).ok_or_else(invalid)?;
+                    if key >> 3 == 1 {
+                        status = v;
+                    }
+                }
+                1 if src.remaining() >= 8 => src.advance(8),
+                2 => {
+                    let len = get_varint(src).ok_or_else(invalid)? as usize;
+                    if src.remaining() < len {
+                        return Err(invalid());
+                    }
+                    src.advance(len);
+                }
+                5 if src.remaining() >= 4 => src.advance(4),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Some(status))
+    }
+}
+
+fn put_varint(dst: &mut impl BufMut, mut v: u64) {
+    while v >= 0x80 {
+        dst.put_u8(v as u8 | 0x80);
+        v >>= 7;
+    }
+    dst.put_u8(v as u8);
+}
+
+fn get_varint(src: &mut impl Buf) -> Option<u64> {
+    let mut v = 0;
+    for shift in (0..64).step_by(7) {
+        if !src.has_remaining() {
+            return None;
+        }
+        let b = src.get_u8();
+        v |= ((b & 0x7f) as u64) << shift;
+        if b < 0x80 {
+            return Some(v);
+        }
+    }
+    None
+}
@@ -0,0 +1,101 @@
+//! the idle queue. flume's unbounded channel with feature `flume` (default), else an internal
+//! mutex + VecDeque + Notify queue with the same semantics, for builds with only std + tokio
+
+#[cfg(feature = "flume")]
+pub(crate) use flume::{unbounded, Receiver, RecvError, Sender, TryRecvError};
+
+#[cfg(not(feature = "flume"))]
+pub(crate) use internal::{unbounded, Receiver, RecvError, Sender, TryRecvError};
+
+#[cfg(not(feature = "flume"))]
+mod internal {
+    use std::collections::VecDeque;
+    use std::fmt::{Debug, Formatter};
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::Notify;
+
+    struct Shared<T> {
+        queue: Mutex<VecDeque<T>>,
+        /// one permit per pushed value, receivers are woken in FIFO order
+        pushed: Notify,
+    }
+
+    /// the receivers live as long as the senders in a pool, so sending never fails
+    #[derive(Debug)]
+    pub(crate) struct SendError<T>(pub T);
+
+    /// nothing to receive
+    #[derive(Debug)]
+    pub(crate) struct TryRecvError;
+
+    /// never returned, `recv_async` waits for a value
+    #[derive(Debug)]
+    pub(crate) struct RecvError;
+
+    pub(crate) struct Sender<T> {
+        shared: Arc<Shared<T>>,
+    }
+
+    pub(crate) struct Receiver<T> {
+        shared: Arc<Shared<T>>,
+    }
+
+    pub(crate) fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            pushed: Notify::new(),
+        });
+        (
+            Sender {
+                shared: shared.clone(),
+            },
+            Receiver { shared },
+        )
+    }
+
+    impl<T> Sender<T> {
+        pub(crate) fn send(&self, v: T) -> Result<(), SendError<T>> {
+            self.shared.queue.lock().unwrap().push_back(v);
+            self.shared.pushed.notify_one();
+            Ok(())
+        }
+
+        pub(crate) fn len(&self) -> usize {
+            self.shared.queue.lock().unwrap().len()
+        }
+    }
+
+    impl<T> Debug for Sender<T> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Sender").finish_non_exhaustive()
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub(crate) fn try_recv(&self) -> Result<T, TryRecvError> {
+            self.shared
+                .queue
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or(TryRecvError)
+        }
+
+        pub(crate) async fn recv_async(&self) -> Result<T, RecvError> {
+            loop {
+                let pushed = self.shared.pushed.notified();
+                tokio::pin!(pushed);
+                //registered before looking, a value sent in between is not missed
+                pushed.as_mut().enable();
+                if let Ok(v) = self.try_recv() {
+                    return Ok(v);
+                }
+                pushed.await;
+            }
+        }
+
+        pub(crate) fn is_empty(&self) -> bool {
+            self.shared.queue.lock().unwrap().is_empty()
+        }
+    }
+}
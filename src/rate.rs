@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// longest window `RollingRate::per_sec` can look back on, in seconds
+pub const RATE_WINDOW_SECS: u64 = 60;
+
+struct Bucket {
+    /// second since `RollingRate::start` this bucket counts for
+    second: AtomicU64,
+    count: AtomicU64,
+}
+
+/// events per second over the last seconds, one bucket per second.
+/// lock free and allocation free, a count racing a bucket reuse may be lost
+pub(crate) struct RollingRate {
+    start: Instant,
+    buckets: [Bucket; RATE_WINDOW_SECS as usize],
+}
+
+impl RollingRate {
+    pub(crate) fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            buckets: std::array::from_fn(|_| Bucket {
+                second: AtomicU64::new(u64::MAX),
+                count: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub(crate) fn record(&self) {
+        let second = self.start.elapsed().as_secs();
+        let bucket = &self.buckets[(second % RATE_WINDOW_SECS) as usize];
+        let seen = bucket.second.load(Ordering::Relaxed);
+        if seen != second
+            && bucket
+                .second
+                .compare_exchange(seen, second, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            bucket.count.store(0, Ordering::Relaxed);
+        }
+        bucket.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// average per second over the last `window` (whole seconds, at most `RATE_WINDOW_SECS`),
+    /// the current second included
+    pub(crate) fn per_sec(&self, window: Duration) -> f64 {
+        let window = window.as_secs().clamp(1, RATE_WINDOW_SECS);
+        let now = self.start.elapsed().as_secs();
+        let count: u64 = self
+            .buckets
+            .iter()
+            .filter(|v| {
+                let second = v.second.load(Ordering::Relaxed);
+                second <= now && now - second < window
+            })
+            .map(|v| v.count.load(Ordering::Relaxed))
+            .sum();
+        count as f64 / window as f64
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// token bucket shared by all acquisitions of a pool, see `Pool::set_retry_budget()`.
+/// the balance is kept in successes, a retry cost `per_token` of them
+#[derive(Debug, Default)]
+pub(crate) struct RetryBudget {
+    /// 0 means disabled
+    max: AtomicU64,
+    per_token: AtomicU64,
+    balance: AtomicU64,
+}
+
+impl RetryBudget {
+    /// start with a full bucket
+    pub(crate) fn set(&self, max: u64, per_token: u64) {
+        let per_token = per_token.max(1);
+        self.per_token.store(per_token, Ordering::SeqCst);
+        self.max.store(max, Ordering::SeqCst);
+        self.balance
+            .store(max.saturating_mul(per_token), Ordering::SeqCst);
+    }
+
+    /// (max tokens, successes per token)
+    pub(crate) fn get(&self) -> (u64, u64) {
+        (
+            self.max.load(Ordering::SeqCst),
+            self.per_token.load(Ordering::SeqCst).max(1),
+        )
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.max.load(Ordering::Relaxed) != 0
+    }
+
+    pub(crate) fn tokens(&self) -> u64 {
+        let (_, per_token) = self.get();
+        self.balance.load(Ordering::SeqCst) / per_token
+    }
+
+    /// a success refill the bucket
+    pub(crate) fn deposit(&self) {
+        let (max, per_token) = self.get();
+        if max == 0 {
+            return;
+        }
+        let cap = max.saturating_mul(per_token);
+        _ = self
+            .balance
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| {
+                (v < cap).then_some(v + 1)
+            });
+    }
+
+    /// take a token for a retry, false when the bucket is empty or disabled
+    pub(crate) fn withdraw(&self) -> bool {
+        let (max, per_token) = self.get();
+        max != 0
+            && self
+                .balance
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| {
+                    v.checked_sub(per_token)
+                })
+                .is_ok()
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{Manager, Pool};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 3600;
+
+/// a daily time window with its own max_open and min_idle. `start`/`end` are times of day,
+/// a window ending before it starts wraps midnight (e.g. 22:00 to 06:00)
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CapacityWindow {
+    pub start: Duration,
+    pub end: Duration,
+    pub max_open: u64,
+    /// idle connections kept warm during the window, 0 means none
+    pub min_idle: u64,
+}
+
+impl CapacityWindow {
+    /// from `start_hour` (inclusive) to `end_hour` (exclusive)
+    pub fn hours(start_hour: u64, end_hour: u64, max_open: u64) -> Self {
+        Self {
+            start: Duration::from_secs(start_hour * 3600),
+            end: Duration::from_secs(end_hour * 3600),
+            max_open,
+            min_idle: 0,
+        }
+    }
+
+    pub fn min_idle(mut self, n: u64) -> Self {
+        self.min_idle = n;
+        self
+    }
+
+    pub fn contains(&self, time_of_day: Duration) -> bool {
+        if self.start <= self.end {
+            self.start <= time_of_day && time_of_day < self.end
+        } else {
+            self.start <= time_of_day || time_of_day < self.end
+        }
+    }
+}
+
+/// max_open and min_idle by time of day, see `Pool::schedule_capacity()`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CapacitySchedule {
+    /// the first window containing the time wins
+    pub windows: Vec<CapacityWindow>,
+    /// max_open outside every window
+    pub default_max_open: u64,
+    /// min_idle outside every window
+    pub default_min_idle: u64,
+    /// seconds east of UTC the windows are written in, e.g. 8 * 3600 for UTC+8
+    pub utc_offset: i64,
+}
+
+impl CapacitySchedule {
+    pub fn new(default_max_open: u64) -> Self {
+        Self {
+            windows: vec![],
+            default_max_open,
+            default_min_idle: 0,
+            utc_offset: 0,
+        }
+    }
+
+    pub fn window(mut self, window: CapacityWindow) -> Self {
+        self.windows.push(window);
+        self
+    }
+
+    pub fn default_min_idle(mut self, n: u64) -> Self {
+        self.default_min_idle = n;
+        self
+    }
+
+    pub fn utc_offset(mut self, secs: i64) -> Self {
+        self.utc_offset = secs;
+        self
+    }
+
+    /// the window containing `time`, None outside every window
+    pub fn window_at(&self, time: SystemTime) -> Option<&CapacityWindow> {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64
+            + self.utc_offset;
+        let time_of_day = Duration::from_secs(secs.rem_euclid(DAY as i64) as u64);
+        self.windows.iter().find(|v| v.contains(time_of_day))
+    }
+
+    /// the max_open the schedule gives at `time`
+    pub fn max_open_at(&self, time: SystemTime) -> u64 {
+        self.window_at(time)
+            .map(|v| v.max_open)
+            .unwrap_or(self.default_max_open)
+    }
+
+    /// the min_idle the schedule gives at `time`
+    pub fn min_idle_at(&self, time: SystemTime) -> u64 {
+        self.window_at(time)
+            .map(|v| v.min_idle)
+            .unwrap_or(self.default_min_idle)
+    }
+}
+
+impl<M: Manager> Pool<M> {
+    /// apply the max_open of `schedule` now and whenever it changes, checking every `interval`.
+    /// a `set_max_open` in between holds until the next window boundary.
+    /// the idle connections are topped up to the min_idle of the schedule every `interval`,
+    /// new ones are connected and checked while there is room under max_open.
+    /// stops when the pool is dropped or the handle aborted. must be called inside a tokio runtime
+    pub fn schedule_capacity(
+        &self,
+        schedule: CapacitySchedule,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        let pool = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut last = None;
+            loop {
+                let Some(inner) = pool.upgrade() else {
+                    break;
+                };
+                let pool = Pool::from_inner(inner);
+                let now = SystemTime::now();
+                let max_open = schedule.max_open_at(now);
+                if last != Some(max_open) {
+                    last = Some(max_open);
+                    #[cfg(feature = "log")]
+                    log::info!(target: "fast_pool", "pool {:x} scheduled max_open: {}", pool.id(), max_open);
+                    pool.set_max_open(max_open);
+                }
+                let min_idle = schedule.min_idle_at(now);
+                if min_idle != 0 && pool.state().idle < min_idle {
+                    //the Manager futures need not be Send, they run on a blocking thread
+                    //driven by this runtime. a failed connect is recorded by the pool,
+                    //the next tick tries again
+                    let handle = tokio::runtime::Handle::current();
+                    let warm = pool.clone();
+                    _ = tokio::task::spawn_blocking(move || {
+                        handle.block_on(warm.wait_warm(min_idle, interval)).is_ok()
+                    })
+                    .await;
+                }
+                drop(pool);
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{ConnectionBox, Manager, Pool, PoolError};
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::MutexGuard;
+
+type Slot<M> = Arc<tokio::sync::Mutex<Option<ConnectionBox<M>>>>;
+type WeakSlot<M> = Weak<tokio::sync::Mutex<Option<ConnectionBox<M>>>>;
+
+/// tracks the connections acquired through it, and returns the ones still held
+/// when it is dropped or `release_all()` is called. see `Pool::scope()`
+pub struct Scope<M: Manager> {
+    pool: Pool<M>,
+    held: Mutex<Vec<WeakSlot<M>>>,
+}
+
+impl<M: Manager> Scope<M> {
+    pub(crate) fn new(pool: Pool<M>) -> Self {
+        Self {
+            pool,
+            held: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub async fn get(&self) -> Result<ScopedConnection<M>, PoolError<M::Error>> {
+        self.get_timeout(None).await
+    }
+
+    pub async fn get_timeout(
+        &self,
+        d: Option<Duration>,
+    ) -> Result<ScopedConnection<M>, PoolError<M::Error>> {
+        let conn = self.pool.get_timeout(d).await?;
+        let slot: Slot<M> = Arc::new(tokio::sync::Mutex::new(Some(conn)));
+        let mut held = self.held.lock().unwrap();
+        held.retain(|v| v.strong_count() != 0);
+        held.push(Arc::downgrade(&slot));
+        Ok(ScopedConnection { slot })
+    }
+
+    /// return every connection of this scope still held to the pool, the `ScopedConnection`s
+    /// are left empty. a connection locked right now is in use and skipped, it goes back
+    /// when its `ScopedConnection` is dropped. return the number of returned connections
+    pub fn release_all(&self) -> u64 {
+        let mut released = 0;
+        self.held.lock().unwrap().retain(|v| {
+            let Some(slot) = v.upgrade() else {
+                return false;
+            };
+            let in_use = match slot.try_lock() {
+                Ok(mut conn) => {
+                    if conn.take().is_some() {
+                        released += 1;
+                    }
+                    false
+                }
+                Err(_) => true,
+            };
+            in_use
+        });
+        released
+    }
+
+    /// connections of this scope still held, locked ones included
+    pub fn held(&self) -> u64 {
+        self.held
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.upgrade())
+            .filter(|v| v.try_lock().map(|v| v.is_some()).unwrap_or(true))
+            .count() as u64
+    }
+}
+
+impl<M: Manager> Drop for Scope<M> {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+impl<M: Manager> Debug for Scope<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scope")
+            .field("pool", &self.pool)
+            .field("held", &self.held())
+            .finish()
+    }
+}
+
+/// connection acquired through a `Scope`, empty once the scope released it
+pub struct ScopedConnection<M: Manager> {
+    slot: Slot<M>,
+}
+
+impl<M: Manager> ScopedConnection<M> {
+    /// the connection, None if its scope already released it.
+    /// the scope does not release it while the guard is held, it can be kept across `.await`
+    pub async fn lock(&self) -> MutexGuard<'_, Option<ConnectionBox<M>>> {
+        self.slot.lock().await
+    }
+
+    /// false while the connection is locked
+    pub fn is_released(&self) -> bool {
+        self.slot.try_lock().map(|v| v.is_none()).unwrap_or(false)
+    }
+
+    /// take the connection out of the scope, it is not released with the scope anymore
+    pub async fn detach(self) -> Option<ConnectionBox<M>> {
+        self.slot.lock().await.take()
+    }
+}
+
+impl<M: Manager> Drop for ScopedConnection<M> {
+    fn drop(&mut self) {
+        //return the connection now, the scope only keeps a weak reference.
+        //locked means `release_all` is taking it right now
+        if let Ok(mut conn) = self.slot.try_lock() {
+            drop(conn.take());
+        }
+    }
+}
+
+impl<M: Manager> Debug for ScopedConnection<M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScopedConnection")
+            .field("released", &self.is_released())
+            .finish()
+    }
+}
@@ -0,0 +1,52 @@
+//! durations of `PoolConfig` as human friendly strings: written like "1m 30s",
+//! read from "30s", "5m", "100ms", a number of milliseconds or serde's `{ secs, nanos }`
+
+use serde::{Deserialize, Deserializer, Serializer};
+use std::time::Duration;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Text(String),
+    Millis(u64),
+    Std(Duration),
+}
+
+impl Repr {
+    fn into_duration<E: serde::de::Error>(self) -> Result<Duration, E> {
+        match self {
+            Repr::Text(v) => humantime::parse_duration(v.trim()).map_err(E::custom),
+            Repr::Millis(v) => Ok(Duration::from_millis(v)),
+            Repr::Std(v) => Ok(v),
+        }
+    }
+}
+
+pub(crate) fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(&humantime::format_duration(*d))
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+    Repr::deserialize(d)?.into_duration()
+}
+
+pub(crate) mod option {
+    use super::Repr;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub(crate) fn serialize<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        match d {
+            Some(d) => s.serialize_some(&humantime::format_duration(*d).to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<Repr>::deserialize(d)?
+            .map(Repr::into_duration)
+            .transpose()
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{Manager, Pool};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// resolve on ctrl-c (SIGINT), or SIGTERM on unix
+async fn terminated() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(_) => _ = tokio::signal::ctrl_c().await,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+impl<M: Manager> Pool<M> {
+    /// `shutdown(Some(grace))` the pool on ctrl-c (SIGINT) or SIGTERM, enabled by feature `signal`.
+    /// the handler replaces the default one, the process keeps running after the shutdown.
+    /// must be called inside a tokio runtime
+    pub fn shutdown_on_ctrl_c(&self, grace: Duration) -> tokio::task::JoinHandle<()>
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+        M::Error: Send,
+    {
+        let pool = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            terminated().await;
+            let Some(inner) = pool.upgrade() else {
+                return;
+            };
+            let pool = Pool::from_inner(inner);
+            //the Manager futures need not be Send, drive the shutdown on a blocking thread
+            let handle = tokio::runtime::Handle::current();
+            let shutting = pool.clone();
+            let Ok(_result) = tokio::task::spawn_blocking(move || {
+                handle.block_on(shutting.shutdown(Some(grace)))
+            })
+            .await
+            else {
+                return;
+            };
+            #[cfg(feature = "log")]
+            match _result {
+                Ok(_) => log::info!(target: "fast_pool", "pool {:x} shut down", pool.id()),
+                Err(_) => log::warn!(
+                    target: "fast_pool",
+                    "pool {:x} shut down with connections still in use after {:?}",
+                    pool.id(),
+                    grace
+                ),
+            }
+        })
+    }
+}
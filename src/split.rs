@@ -0,0 +1,190 @@
+use crate::{ConnectionBox, Manager};
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// a connection that can split into independent read and write halves (framed TCP,
+/// QUIC streams, ...), see `ConnectionBox::split()`
+pub trait Splittable: Sized {
+    type Read;
+    type Write;
+
+    fn split(self) -> (Self::Read, Self::Write);
+
+    /// put back together the halves `split` returned
+    fn unsplit(read: Self::Read, write: Self::Write) -> Self;
+}
+
+type ReadOf<M> = <<M as Manager>::Connection as Splittable>::Read;
+type WriteOf<M> = <<M as Manager>::Connection as Splittable>::Write;
+
+/// the checkout both halves share, the connection goes back to the pool once both are dropped
+struct Joint<M: Manager>
+where
+    M::Connection: Splittable,
+{
+    guard: ConnectionBox<M>,
+    read: Mutex<Option<ReadOf<M>>>,
+    write: Mutex<Option<WriteOf<M>>>,
+}
+
+impl<M: Manager> Drop for Joint<M>
+where
+    M::Connection: Splittable,
+{
+    fn drop(&mut self) {
+        let read = self.read.get_mut().unwrap().take();
+        let write = self.write.get_mut().unwrap().take();
+        if let (Some(read), Some(write)) = (read, write) {
+            self.guard.inner = Some(Splittable::unsplit(read, write));
+        }
+    }
+}
+
+/// read half of a split `ConnectionBox`
+pub struct ReadHalf<M: Manager>
+where
+    M::Connection: Splittable,
+{
+    half: Option<ReadOf<M>>,
+    joint: Arc<Joint<M>>,
+}
+
+/// write half of a split `ConnectionBox`
+pub struct WriteHalf<M: Manager>
+where
+    M::Connection: Splittable,
+{
+    half: Option<WriteOf<M>>,
+    joint: Arc<Joint<M>>,
+}
+
+impl<M: Manager> ConnectionBox<M>
+where
+    M::Connection: Splittable,
+{
+    /// split the connection into halves usable from different tasks. they share this checkout's
+    /// capacity slot, the connection is put back together and returned once both are dropped
+    pub fn split(mut self) -> (ReadHalf<M>, WriteHalf<M>) {
+        let (read, write) = self
+            .inner
+            .take()
+            .expect("connection taken out of the box")
+            .split();
+        let joint = Arc::new(Joint {
+            guard: self,
+            read: Mutex::new(None),
+            write: Mutex::new(None),
+        });
+        (
+            ReadHalf {
+                half: Some(read),
+                joint: joint.clone(),
+            },
+            WriteHalf {
+                half: Some(write),
+                joint,
+            },
+        )
+    }
+}
+
+impl<M: Manager> ReadHalf<M>
+where
+    M::Connection: Splittable,
+{
+    /// id of the connection in its pool
+    pub fn connection_id(&self) -> u64 {
+        self.joint.guard.connection_id()
+    }
+}
+
+impl<M: Manager> WriteHalf<M>
+where
+    M::Connection: Splittable,
+{
+    /// id of the connection in its pool
+    pub fn connection_id(&self) -> u64 {
+        self.joint.guard.connection_id()
+    }
+}
+
+impl<M: Manager> Deref for ReadHalf<M>
+where
+    M::Connection: Splittable,
+{
+    type Target = ReadOf<M>;
+
+    fn deref(&self) -> &Self::Target {
+        self.half.as_ref().unwrap()
+    }
+}
+
+impl<M: Manager> DerefMut for ReadHalf<M>
+where
+    M::Connection: Splittable,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.half.as_mut().unwrap()
+    }
+}
+
+impl<M: Manager> Deref for WriteHalf<M>
+where
+    M::Connection: Splittable,
+{
+    type Target = WriteOf<M>;
+
+    fn deref(&self) -> &Self::Target {
+        self.half.as_ref().unwrap()
+    }
+}
+
+impl<M: Manager> DerefMut for WriteHalf<M>
+where
+    M::Connection: Splittable,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.half.as_mut().unwrap()
+    }
+}
+
+impl<M: Manager> Drop for ReadHalf<M>
+where
+    M::Connection: Splittable,
+{
+    fn drop(&mut self) {
+        *self.joint.read.lock().unwrap() = self.half.take();
+    }
+}
+
+impl<M: Manager> Drop for WriteHalf<M>
+where
+    M::Connection: Splittable,
+{
+    fn drop(&mut self) {
+        *self.joint.write.lock().unwrap() = self.half.take();
+    }
+}
+
+impl<M: Manager> Debug for ReadHalf<M>
+where
+    M::Connection: Splittable,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadHalf")
+            .field("connection_id", &self.connection_id())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M: Manager> Debug for WriteHalf<M>
+where
+    M::Connection: Splittable,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WriteHalf")
+            .field("connection_id", &self.connection_id())
+            .finish_non_exhaustive()
+    }
+}
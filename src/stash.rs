@@ -0,0 +1,38 @@
+use crate::partition::thread_slot;
+use std::sync::Mutex;
+
+/// stripes of a `ReturnStash`, threads are spread over them round-robin
+const STRIPES: usize = 8;
+
+/// returns parked on a stripe before they are flushed to the idle queue together
+pub const RETURN_BATCH: usize = 16;
+
+/// returned connections parked per thread, so simultaneous returns don't all contend
+/// on the idle queue. a stripe keeps its capacity, flushing it does not allocate
+pub(crate) struct ReturnStash<T> {
+    stripes: [Mutex<Vec<T>>; STRIPES],
+}
+
+impl<T> ReturnStash<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            stripes: std::array::from_fn(|_| Mutex::new(Vec::new())),
+        }
+    }
+
+    /// park `v` on the stripe of this thread, handing the whole stripe to `flush` once it is full
+    pub(crate) fn push(&self, v: T, flush: impl FnMut(T)) {
+        let mut stripe = self.stripes[thread_slot() % STRIPES].lock().unwrap();
+        stripe.push(v);
+        if stripe.len() >= RETURN_BATCH {
+            stripe.drain(..).for_each(flush);
+        }
+    }
+
+    /// hand every parked value to `flush`
+    pub(crate) fn drain(&self, mut flush: impl FnMut(T)) {
+        for stripe in &self.stripes {
+            stripe.lock().unwrap().drain(..).for_each(&mut flush);
+        }
+    }
+}
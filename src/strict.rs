@@ -0,0 +1,9 @@
+/// panic on `$cond` being false with feature `strict`, to turn a misuse that would silently
+/// corrupt the pool accounting into an actionable failure. compiled out without the feature
+macro_rules! strict {
+    ($cond:expr, $($arg:tt)+) => {
+        if cfg!(feature = "strict") && !$cond {
+            panic!("fast_pool strict mode: {}", format_args!($($arg)+));
+        }
+    };
+}
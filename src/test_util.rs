@@ -0,0 +1,121 @@
+//! hooks for deterministic concurrency tests, enabled by feature `test-util`.
+//!
+//! ```rust
+//! # use fast_pool::{AcquirePhase, Manager, Pool};
+//! # pub struct TestManager {}
+//! # impl Manager for TestManager {
+//! #     type Connection = String;
+//! #     type Error = String;
+//! #     async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+//! #         Ok(String::new())
+//! #     }
+//! #     async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+//! #         Ok(())
+//! #     }
+//! # }
+//! # #[tokio::main]
+//! # async fn main() {
+//! let p = Pool::new(TestManager {});
+//! let pause = p.pause_at(AcquirePhase::Check);
+//! let p2 = p.clone();
+//! let task = tokio::spawn(async move { p2.get().await.is_ok() });
+//! //the task is now stopped right before Manager::check
+//! pause.wait_arrived(1).await;
+//! assert_eq!(p.state().in_use, 0);
+//! pause.release(1);
+//! assert!(task.await.unwrap());
+//! # }
+//! ```
+use crate::AcquirePhase;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, Semaphore};
+
+/// pause points registered on a pool
+#[derive(Debug, Default)]
+pub(crate) struct Hooks {
+    gates: Mutex<HashMap<AcquirePhase, Arc<Gate>>>,
+}
+
+impl Hooks {
+    /// called by the acquire path before entering `phase`, block while a `PausePoint` hold it
+    pub(crate) async fn reached(&self, phase: AcquirePhase) {
+        let gate = self.gates.lock().unwrap().get(&phase).cloned();
+        if let Some(gate) = gate {
+            gate.arrived.fetch_add(1, Ordering::SeqCst);
+            gate.notify.notify_waiters();
+            //closed semaphore means the PausePoint was dropped, just continue
+            if let Ok(permit) = gate.release.acquire().await {
+                permit.forget();
+            }
+        }
+    }
+
+    pub(crate) fn pause_at(self: &Arc<Self>, phase: AcquirePhase) -> PausePoint {
+        let gate = Arc::new(Gate {
+            arrived: AtomicU64::new(0),
+            notify: Notify::new(),
+            release: Semaphore::new(0),
+        });
+        if let Some(old) = self.gates.lock().unwrap().insert(phase, gate.clone()) {
+            old.release.close();
+        }
+        PausePoint {
+            phase,
+            gate,
+            hooks: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Gate {
+    arrived: AtomicU64,
+    notify: Notify,
+    release: Semaphore,
+}
+
+/// stop acquisitions right before an `AcquirePhase` until released.
+/// dropping it releases every paused task and removes the pause
+#[derive(Debug)]
+pub struct PausePoint {
+    phase: AcquirePhase,
+    gate: Arc<Gate>,
+    hooks: Arc<Hooks>,
+}
+
+impl PausePoint {
+    /// number of acquisitions that reached the phase so far
+    pub fn arrived(&self) -> u64 {
+        self.gate.arrived.load(Ordering::SeqCst)
+    }
+
+    /// wait until at least `n` acquisitions reached the phase
+    pub async fn wait_arrived(&self, n: u64) {
+        loop {
+            let notified = self.gate.notify.notified();
+            if self.arrived() >= n {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// let `n` paused (or future) acquisitions pass
+    pub fn release(&self, n: usize) {
+        self.gate.release.add_permits(n);
+    }
+}
+
+impl Drop for PausePoint {
+    fn drop(&mut self) {
+        let mut gates = self.hooks.gates.lock().unwrap();
+        if let Some(gate) = gates.get(&self.phase) {
+            if Arc::ptr_eq(gate, &self.gate) {
+                gates.remove(&self.phase);
+            }
+        }
+        self.gate.release.close();
+    }
+}
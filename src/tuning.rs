@@ -0,0 +1,134 @@
+use crate::{Manager, Pool};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// latencies `LatencySamples` keep, the oldest is overwritten
+const SAMPLES: usize = 256;
+/// fewer samples than this leave the timeout unchanged
+const MIN_SAMPLES: usize = 16;
+
+/// the last `SAMPLES` latencies of successful connects or checks.
+/// lock free, a sample racing another one on the same slot may be lost
+pub(crate) struct LatencySamples {
+    next: AtomicU64,
+    nanos: [AtomicU64; SAMPLES],
+}
+
+impl LatencySamples {
+    pub(crate) fn new() -> Self {
+        Self {
+            next: AtomicU64::new(0),
+            nanos: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    pub(crate) fn record(&self, latency: Duration) {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) as usize % SAMPLES;
+        self.nanos[i].store((latency.as_nanos() as u64).max(1), Ordering::Relaxed);
+    }
+
+    /// the latency `p` (0.0..=1.0) of the samples are below, None without enough samples
+    pub(crate) fn percentile(&self, p: f64) -> Option<Duration> {
+        let mut samples: Vec<u64> = self
+            .nanos
+            .iter()
+            .map(|v| v.load(Ordering::Relaxed))
+            .filter(|v| *v != 0)
+            .collect();
+        if samples.len() < MIN_SAMPLES {
+            return None;
+        }
+        samples.sort_unstable();
+        let rank = (p.clamp(0.0, 1.0) * samples.len() as f64).ceil() as usize;
+        Some(Duration::from_nanos(
+            samples[rank.clamp(1, samples.len()) - 1],
+        ))
+    }
+}
+
+/// derive `connect_timeout` and `check_timeout` from the observed latencies,
+/// see `Pool::set_timeout_tuning()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeoutTuning {
+    /// latency percentile the timeout is based on, 0.99 is p99
+    pub percentile: f64,
+    /// timeout = percentile latency * factor
+    pub factor: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_duration"))]
+    pub min: Duration,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_duration"))]
+    pub max: Duration,
+    /// time between two updates
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_duration"))]
+    pub interval: Duration,
+}
+
+impl Default for TimeoutTuning {
+    fn default() -> Self {
+        Self {
+            percentile: 0.99,
+            factor: 3.0,
+            min: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl TimeoutTuning {
+    fn timeout(&self, samples: &LatencySamples) -> Option<Duration> {
+        let latency = samples.percentile(self.percentile)?;
+        let timeout = latency.mul_f64(self.factor.max(0.0));
+        Some(timeout.clamp(self.min, self.max.max(self.min)))
+    }
+}
+
+impl<M: Manager> Pool<M> {
+    /// every `tuning.interval`, set `connect_timeout` and `check_timeout` to the latency
+    /// percentile of the recent successful connects and checks times `factor`, within
+    /// `min` and `max`. a timeout is left as is until enough samples are collected.
+    /// None stop the updates and keep the last timeouts (default).
+    /// must be called inside a tokio runtime, the updates run on a spawned task
+    pub fn set_timeout_tuning(&self, tuning: Option<TimeoutTuning>)
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+    {
+        let generation = self
+            .inner
+            .timeout_tuning_generation
+            .fetch_add(1, Ordering::SeqCst)
+            + 1;
+        *self.inner.timeout_tuning.write().unwrap() = tuning;
+        let Some(tuning) = tuning else {
+            return;
+        };
+        let pool = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tuning.interval.max(Duration::from_millis(1))).await;
+                //pool dropped
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                if pool.timeout_tuning_generation.load(Ordering::SeqCst) != generation {
+                    break;
+                }
+                if let Some(d) = tuning.timeout(&pool.connect_latency) {
+                    pool.connect_timeout
+                        .store((d.as_nanos() as u64).max(1), Ordering::SeqCst);
+                }
+                if let Some(d) = tuning.timeout(&pool.check_latency) {
+                    pool.check_timeout
+                        .store((d.as_nanos() as u64).max(1), Ordering::SeqCst);
+                }
+            }
+        });
+    }
+
+    pub fn timeout_tuning(&self) -> Option<TimeoutTuning> {
+        *self.inner.timeout_tuning.read().unwrap()
+    }
+}
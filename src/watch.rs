@@ -0,0 +1,85 @@
+use crate::{Manager, Pool, PoolConfig};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// a config file was reloaded, see `Pool::watch_config_file()`
+#[derive(Debug, Clone, PartialEq)]
+//one event per reload, not worth boxing the config listeners match on
+#[allow(clippy::large_enum_variant)]
+pub enum ConfigReloadEvent {
+    /// the file changed and the config was applied
+    Applied { pool_id: u64, config: PoolConfig },
+    /// the file changed but could not be read, parsed or applied, the pool keeps its config
+    Failed { pool_id: u64, error: String },
+}
+
+/// callback receiving every `ConfigReloadEvent`
+pub type ConfigReloadListener = fn(&ConfigReloadEvent);
+
+/// parse a `PoolConfig` file, TOML for a `.toml` extension, JSON otherwise
+pub fn read_config_file(path: &Path) -> Result<PoolConfig, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    match path.extension().and_then(|v| v.to_str()) {
+        Some("toml") => toml::from_str(&text).map_err(|e| e.to_string()),
+        _ => serde_json::from_str(&text).map_err(|e| e.to_string()),
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+impl<M: Manager> Pool<M> {
+    /// check `path` every `interval` and `apply_config` it whenever it changes, starting now.
+    /// stops when the pool is dropped. must be called inside a tokio runtime
+    pub fn watch_config_file(
+        &self,
+        path: impl Into<PathBuf>,
+        interval: Duration,
+        listener: Option<ConfigReloadListener>,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        M: Send + Sync + 'static,
+        M::Connection: Send,
+        M::Error: Display,
+    {
+        let path = path.into();
+        let pool = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut last = None;
+            loop {
+                let Some(inner) = pool.upgrade() else {
+                    break;
+                };
+                let pool = Pool::from_inner(inner);
+                let now = modified(&path);
+                if now.is_some() && now != last {
+                    last = now;
+                    let event = match read_config_file(&path).and_then(|config| {
+                        pool.apply_config(&config)
+                            .map(|_| config)
+                            .map_err(|e| e.to_string())
+                    }) {
+                        Ok(config) => ConfigReloadEvent::Applied {
+                            pool_id: pool.id(),
+                            config,
+                        },
+                        Err(error) => ConfigReloadEvent::Failed {
+                            pool_id: pool.id(),
+                            error,
+                        },
+                    };
+                    #[cfg(feature = "log")]
+                    log::info!(target: "fast_pool", "pool {:x} config reload: {:?}", pool.id(), event);
+                    if let Some(listener) = listener {
+                        listener(&event);
+                    }
+                }
+                drop(pool);
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+}
@@ -0,0 +1,77 @@
+use fast_pool::{Manager, Pool};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// count allocations per thread, so the acquire fast path can be checked allocation free
+/// while other tests run on their own threads
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+fn allocations() -> u64 {
+    ALLOCATIONS.with(|n| n.get())
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+pub struct TestManager {}
+
+impl Manager for TestManager {
+    type Connection = i32;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(0)
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_get_no_allocation() {
+    let p = Pool::new(TestManager {});
+    //warm up, create the connection and grow the idle channel
+    for _ in 0..10 {
+        let _conn = p.get().await.unwrap();
+    }
+    let before = allocations();
+    for _ in 0..1000 {
+        let _conn = p.get().await.unwrap();
+    }
+    assert_eq!(allocations() - before, 0);
+}
+
+#[tokio::test]
+async fn test_get_timeout_no_allocation() {
+    let p = Pool::new(TestManager {});
+    for _ in 0..10 {
+        let _conn = p
+            .get_timeout(Some(std::time::Duration::from_secs(1)))
+            .await
+            .unwrap();
+    }
+    let before = allocations();
+    for _ in 0..1000 {
+        let _conn = p
+            .get_timeout(Some(std::time::Duration::from_secs(1)))
+            .await
+            .unwrap();
+    }
+    assert_eq!(allocations() - before, 0);
+}
@@ -0,0 +1,29 @@
+use fast_pool::{default_max_open, set_default_max_open, Manager, Pool, MAX_OPEN_ENV};
+
+pub struct TestManager {}
+
+impl Manager for TestManager {
+    type Connection = String;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(String::new())
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+//own test binary, the default is process wide
+#[test]
+fn test_default_max_open() {
+    //read once, before the first pool
+    std::env::set_var(MAX_OPEN_ENV, "7");
+    assert_eq!(default_max_open(), 7);
+    assert_eq!(Pool::new(TestManager {}).state().max_open, 7);
+    set_default_max_open(3);
+    assert_eq!(Pool::new(TestManager {}).state().max_open, 3);
+    set_default_max_open(0);
+    assert_eq!(Pool::new(TestManager {}).state().max_open, 7);
+}
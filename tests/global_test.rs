@@ -0,0 +1,60 @@
+use fast_pool::{global_pool, Manager, Pool};
+use std::time::Duration;
+
+pub struct TestManager {}
+
+impl Manager for TestManager {
+    type Connection = String;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(String::new())
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+global_pool!(static POOL: TestManager);
+global_pool!(pub static WARM_POOL: TestManager);
+
+//own test binary, the pools are process wide
+#[tokio::test]
+async fn test_global_pool() {
+    assert!(POOL.get().is_none());
+    let id = POOL
+        .init(Pool::builder(TestManager {}).max_open(4).build())
+        .await
+        .unwrap()
+        .id();
+    //first init wins, the second pool is handed back
+    let second = POOL.init(Pool::new(TestManager {})).await.unwrap_err();
+    assert_ne!(second.id(), id);
+    assert_eq!(POOL.pool().id(), id);
+    assert_eq!(POOL.pool().state().max_open, 4);
+    assert!(POOL.is_initialized());
+    assert!(POOL.pool().get().await.is_ok());
+
+    let mut tasks = vec![];
+    for _ in 0..4 {
+        tasks.push(tokio::spawn(async {
+            WARM_POOL
+                .get_or_init(|| async {
+                    let p = Pool::new(TestManager {});
+                    p.set_max_open(2);
+                    p.wait_warm(2, Duration::from_secs(1)).await.unwrap();
+                    p
+                })
+                .await
+                .id()
+        }));
+    }
+    let mut ids = vec![];
+    for task in tasks {
+        ids.push(task.await.unwrap());
+    }
+    ids.dedup();
+    assert_eq!(ids.len(), 1);
+    assert_eq!(WARM_POOL.pool().state().idle, 2);
+}
@@ -0,0 +1,102 @@
+use fast_pool::plugin::HyperManager;
+use fast_pool::{Pool, PoolError};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::{Bytes, Incoming};
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+
+const HOST: &str = "db.internal:8080";
+
+async fn serve() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(|req: Request<Incoming>| async move {
+                    //like nginx or Go's net/http, HTTP/1.1 without Host is a bad request
+                    let host = req.headers().get(hyper::header::HOST);
+                    let status = match req.uri().path() {
+                        _ if host.is_none_or(|v| v != HOST) => StatusCode::BAD_REQUEST,
+                        "/health" => StatusCode::OK,
+                        _ => StatusCode::SERVICE_UNAVAILABLE,
+                    };
+                    let mut res = Response::new(Empty::<Bytes>::new());
+                    *res.status_mut() = status;
+                    Ok::<_, hyper::Error>(res)
+                });
+                _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl From<&str> for Error {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<hyper::Error> for Error {
+    fn from(value: hyper::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_hyper_manager() {
+    let addr = serve().await;
+    let connect = move || async move {
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(|e| Error(e.to_string()))?;
+        Ok::<_, Error>(TokioIo::new(stream))
+    };
+    let p = Pool::new(
+        HyperManager::<_, Empty<Bytes>, Error>::new(connect).health_check(HOST, "/health"),
+    );
+    p.set_max_open(1);
+    for _ in 0..3 {
+        let mut conn = p.get().await.unwrap();
+        let res = conn
+            .send_request(
+                Request::get("/health")
+                    .header(hyper::header::HOST, HOST)
+                    .body(Empty::new())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        res.into_body().collect().await.unwrap();
+    }
+    //the health checks reused the same connection
+    assert_eq!(p.state().created, 1);
+
+    let p =
+        Pool::new(HyperManager::<_, Empty<Bytes>, Error>::new(connect).health_check(HOST, "/down"));
+    p.set_check_failure_action(|_| fast_pool::FailureAction::DropAndFail);
+    match p.get().await.unwrap_err() {
+        PoolError::CheckFailed(e) => assert_eq!(e.0, "503"),
+        e => panic!("{:?}", e),
+    }
+
+    //a wrong Host is rejected like a missing one
+    let p = Pool::new(
+        HyperManager::<_, Empty<Bytes>, Error>::new(connect).health_check("other", "/health"),
+    );
+    p.set_check_failure_action(|_| fast_pool::FailureAction::DropAndFail);
+    match p.get().await.unwrap_err() {
+        PoolError::CheckFailed(e) => assert_eq!(e.0, "400"),
+        e => panic!("{:?}", e),
+    }
+}
@@ -1,8 +1,23 @@
-use fast_pool::{Manager, Pool};
+//the baseline tests predate the lint gate, keep them as written
+#![allow(clippy::bool_assert_comparison, clippy::comparison_to_empty)]
+use fast_pool::plugin::{
+    BlockingError, BoxManager, BoxPool, ClosureManager, SyncManager, SyncManagerBridge,
+};
+#[cfg(feature = "config-watch")]
+use fast_pool::ConfigReloadEvent;
+use fast_pool::{
+    Acquire, AcquireOutcome, AcquirePhase, CheckMode, CloseCounts, CloseReason, ConnectionEvent,
+    ConnectionState, DiagnosticStatus, ErrorCode, ErrorCodeExt, ErrorEvent, ErrorHandler,
+    ErrorKind, FailureAction, GroupStrategy, LowBudgetAction, Manager, PanicPolicy,
+    PartitionedPool, Pool, PoolConfig, PoolError, PoolGroup, SaturationEvent, Splittable,
+    StarvationEvent, StrictPoolBuilder, TimeoutTuning, UsageLimit, RATE_WINDOW_SECS, UNLIMITED,
+};
+#[cfg(feature = "schedule")]
+use fast_pool::{CapacitySchedule, CapacityWindow};
 use std::ops::Deref;
 use std::time::Duration;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TestManager {}
 
 impl Manager for TestManager {
@@ -228,6 +243,2286 @@ async fn test_pool_wait() {
     });
     tokio::time::sleep(Duration::from_secs(1)).await;
     println!("{:?}", p.state());
-    assert_eq!(p.state().waits, 2);
+    assert_eq!(p.state().waiting, 2);
+    assert_eq!(p.state().acquires_in_flight, 2);
     drop(v);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(p.state().waiting, 0);
+    assert_eq!(p.state().acquires_in_flight, 0);
+}
+
+#[tokio::test]
+async fn test_pool_waiters() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    let v = p.get().await.unwrap();
+    for _ in 0..2 {
+        let p1 = p.clone();
+        tokio::spawn(async move {
+            p1.get().await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    //the longest waiting first
+    let waiters = p.waiters();
+    assert_eq!(waiters.len(), 2);
+    assert!(waiters[0].waited >= waiters[1].waited);
+    assert!(waiters[1].waited >= Duration::from_millis(500));
+    drop(v);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(p.waiters().is_empty());
+}
+
+#[tokio::test]
+async fn test_pending_acquires() {
+    let p = Pool::new(ClosureManager::new(
+        || async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok::<_, String>(String::new())
+        },
+        |_: &mut String| async { Ok(()) },
+    ));
+    p.set_max_open(1);
+    for _ in 0..2 {
+        let p1 = p.clone();
+        tokio::spawn(async move {
+            drop(p1.get().await.unwrap());
+        });
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let pending = p.pending_acquires();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].stage, AcquirePhase::Connect);
+    assert_eq!(pending[1].stage, AcquirePhase::Wait);
+    assert!(pending[0].elapsed >= pending[0].waited);
+    assert_eq!(p.waiters().len(), 1);
+    let state = p.state();
+    assert_eq!((state.connecting, state.waiting), (1, 1));
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert!(p.pending_acquires().is_empty());
+    assert_eq!(p.state().connecting, 0);
+}
+
+#[tokio::test]
+async fn test_cancel_waiters() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    let conn = p.get().await.unwrap();
+    let mut tasks = vec![];
+    for _ in 0..2 {
+        let p1 = p.clone();
+        tasks.push(tokio::spawn(async move { p1.get().await.map(|_| ()) }));
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(p.cancel_waiters(), 2);
+    for task in tasks {
+        let e = task.await.unwrap().unwrap_err();
+        assert_eq!(e.error_code(), Some(ErrorCode::Cancelled));
+    }
+    //the pool keeps serving, the connection in use is untouched
+    assert_eq!(p.state().in_use, 1);
+    drop(conn);
+    assert!(p.get().await.is_ok());
+    assert_eq!(p.state().connections, 1);
+}
+
+#[tokio::test]
+async fn test_check_failure_drop_and_fail() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_check_failure_action(|_| FailureAction::DropAndFail);
+    let mut conn = p.get().await.unwrap();
+    *conn.inner.as_mut().unwrap() = "error".to_string();
+    drop(conn);
+    assert_eq!(
+        p.get().await.unwrap_err(),
+        PoolError::CheckFailed("error".to_string())
+    );
+    assert_eq!(p.state().connections, 0);
+    assert!(p.get().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_check_failure_keep_and_fail() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_check_failure_action(|_| FailureAction::KeepAndFail);
+    let mut conn = p.get().await.unwrap();
+    *conn.inner.as_mut().unwrap() = "error".to_string();
+    drop(conn);
+    assert_eq!(
+        p.get().await.unwrap_err(),
+        PoolError::CheckFailed("error".to_string())
+    );
+    assert_eq!(p.state().idle, 1);
+    assert_eq!(p.state().in_use, 0);
+}
+
+#[tokio::test]
+async fn test_max_check_failures() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_check_failure_action(|_| FailureAction::KeepAndFail);
+    p.set_max_check_failures(2);
+    let mut conn = p.get().await.unwrap();
+    *conn.inner.as_mut().unwrap() = "error".to_string();
+    drop(conn);
+    assert!(p.get().await.is_err());
+    assert_eq!(p.state().idle, 1);
+    assert!(p.get().await.is_err());
+    assert_eq!(p.state().connections, 0);
+    assert_eq!(p.get().await.unwrap().deref(), "");
+}
+
+#[tokio::test]
+async fn test_transfer_idle_to() {
+    let old = Pool::new(TestManager {});
+    old.set_max_open(3);
+    let arr = vec![
+        old.get().await.unwrap(),
+        old.get().await.unwrap(),
+        old.get().await.unwrap(),
+    ];
+    drop(arr);
+    assert_eq!(old.state().idle, 3);
+    let new = Pool::new(TestManager {});
+    new.set_max_open(2);
+    assert_eq!(old.transfer_idle_to(&new), 2);
+    assert_eq!(old.state().connections, 1);
+    assert_eq!(new.state().idle, 2);
+    assert_eq!(new.state().connections, 2);
+    assert_eq!(old.transfer_idle_to(&old.clone()), 0);
+}
+
+#[cfg(feature = "send")]
+#[tokio::test]
+async fn test_spawn_generic() {
+    fn spawn_get<M>(p: Pool<M>) -> tokio::task::JoinHandle<bool>
+    where
+        M: Manager + Send + Sync + 'static,
+        M::Connection: Send,
+        M::Error: Send,
+    {
+        tokio::spawn(async move { p.get().await.is_ok() })
+    }
+    let p = Pool::new(TestManager {});
+    assert!(spawn_get(p.clone()).await.unwrap());
+    assert_eq!(p.state().idle, 1);
+}
+
+#[tokio::test]
+async fn test_box_manager() {
+    let p: Pool<BoxManager<String, String>> = Pool::new(BoxManager::new(TestManager {}));
+    p.set_max_open(1);
+    let mut conn = p.get().await.unwrap();
+    assert_eq!(conn.deref(), "");
+    *conn.inner.as_mut().unwrap() = "error".to_string();
+    drop(conn);
+    assert_eq!(p.get().await.unwrap().deref(), "");
+}
+
+#[tokio::test]
+async fn test_downcast() {
+    let p: Pool<BoxManager<String, String>> = Pool::new(BoxManager::new(TestManager {}));
+    assert!(p.manager_as::<TestManager>().is_none());
+    let m = p.manager_as::<BoxManager<String, String>>().unwrap();
+    assert!(m.downcast_ref::<TestManager>().is_some());
+    assert!(m.downcast_ref::<SlowManager>().is_none());
+    let mut conn = p.get().await.unwrap();
+    assert!(conn.downcast_ref::<u64>().is_none());
+    conn.downcast_mut::<String>().unwrap().push_str("error");
+    assert_eq!(conn.downcast_ref::<String>().unwrap(), "error");
+}
+
+#[tokio::test]
+async fn test_close_reason() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(2);
+    let mut arr = vec![p.get().await.unwrap(), p.get().await.unwrap()];
+    *arr[1].inner.as_mut().unwrap() = "error".to_string();
+    p.set_max_open(1);
+    drop(arr);
+    assert_eq!(p.closed(CloseReason::Resized), 1);
+    assert_eq!(p.closed(CloseReason::CheckFailed), 0);
+    p.set_max_open(2);
+    let _conn = p.get().await.unwrap();
+    let _conn2 = p.get().await.unwrap();
+    assert_eq!(p.closed(CloseReason::CheckFailed), 1);
+}
+
+#[tokio::test]
+async fn test_pool_id() {
+    let p = Pool::new(TestManager {});
+    let p2 = Pool::new(TestManager {});
+    assert_eq!(p.id(), p.clone().id());
+    assert_ne!(p.id(), p2.id());
+    assert_eq!(p.state().id, p.id());
+}
+
+#[tokio::test]
+async fn test_pool_config() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(5);
+    p.set_max_check_failures(3);
+    assert_eq!(
+        p.config(),
+        PoolConfig {
+            max_open: 5,
+            max_check_failures: 3,
+            idle_decay: None,
+            max_idle_time: None,
+            conn_max_lifetime: None,
+            reuse_wait: None,
+            starvation_threshold: None,
+            fail_on_starvation: false,
+            saturation_alert: None,
+            max_checking: 0,
+            check_budget_floor: None,
+            low_budget_action: LowBudgetAction::SkipCheck,
+            burst: 0,
+            burst_after: Duration::ZERO,
+            retry_budget: 0,
+            retry_per_token: 1,
+            connect_timeout: None,
+            check_timeout: None,
+            check_mode: CheckMode::Always,
+            panic_policy: PanicPolicy::Recycle,
+        }
+    );
+    assert_eq!(p.config(), p.clone().config());
+}
+
+#[tokio::test]
+async fn test_apply_config() {
+    let p = Pool::new(TestManager {});
+    let mut config = p.export_config();
+    config.max_open = 2;
+    config.idle_decay = Some(Duration::from_secs(60));
+    config.starvation_threshold = Some(Duration::from_secs(1));
+    config.fail_on_starvation = true;
+    p.apply_config(&config).unwrap();
+    assert_eq!(p.config(), config);
+    let mut invalid = config.clone();
+    invalid.max_open = 0;
+    invalid.max_check_failures = 9;
+    assert_eq!(
+        p.apply_config(&invalid).unwrap_err(),
+        PoolError::InvalidConfig("max_open must be greater than 0")
+    );
+    assert_eq!(
+        p.apply_config(&invalid).unwrap_err().to_string(),
+        "INVALID_CONFIG: max_open must be greater than 0"
+    );
+    assert_eq!(p.config(), config);
+    let mut invalid = config.clone();
+    invalid.check_mode = CheckMode::Sample(1.5);
+    assert_eq!(
+        p.apply_config(&invalid).unwrap_err(),
+        PoolError::InvalidConfig("check_mode sample ratio must be between 0 and 1")
+    );
+    invalid.check_mode = CheckMode::Sample(f64::NAN);
+    assert!(p.apply_config(&invalid).is_err());
+    assert_eq!(p.config(), config);
+}
+
+#[test]
+fn test_apply_config_outside_runtime() {
+    let p = Pool::new(TestManager {});
+    let mut config = p.export_config();
+    config.max_open = 1;
+    config.max_idle_time = Some(Duration::from_millis(100));
+    config.saturation_alert = Some(Duration::from_secs(1));
+    p.apply_config(&config).unwrap();
+    assert_eq!(p.config(), config);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        //the sweep starts with the first get()
+        drop(p.get().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(p.closed(CloseReason::IdleTimeout), 1);
+    });
+}
+
+#[test]
+fn test_apply_config_snapshot() {
+    let p = Pool::new(TestManager {});
+    let a = p.export_config();
+    let mut b = a.clone();
+    b.max_open = 7;
+    b.max_check_failures = 3;
+    b.reuse_wait = Some(Duration::from_millis(5));
+    b.burst = 2;
+    b.burst_after = Duration::from_millis(20);
+    b.retry_budget = 4;
+    b.retry_per_token = 2;
+    b.check_mode = CheckMode::IdleTime(Duration::from_secs(1));
+    b.panic_policy = PanicPolicy::Discard;
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let (p, a, b, done) = (p.clone(), a.clone(), b.clone(), done.clone());
+            std::thread::spawn(move || {
+                while !done.load(std::sync::atomic::Ordering::SeqCst) {
+                    let config = p.config();
+                    assert!(config == a || config == b, "{:?}", config);
+                }
+            })
+        })
+        .collect();
+    for i in 0..2000 {
+        p.apply_config(if i % 2 == 0 { &b } else { &a }).unwrap();
+    }
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    for v in readers {
+        v.join().unwrap();
+    }
+    assert_eq!(p.config(), a);
+}
+
+#[tokio::test]
+async fn test_idle_decay() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    let arr = vec![
+        p.get().await.unwrap(),
+        p.get().await.unwrap(),
+        p.get().await.unwrap(),
+    ];
+    drop(arr);
+    p.set_idle_decay(Some(Duration::from_millis(100)));
+    assert_eq!(p.idle_decay(), Some(Duration::from_millis(100)));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(p.state().idle, 2);
+    assert_eq!(p.closed(CloseReason::IdleDecay), 1);
+    p.set_idle_decay(None);
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(p.state().idle, 2);
+}
+
+#[tokio::test]
+async fn test_max_idle_time() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    drop(p.get().await.unwrap());
+    p.set_max_idle_time(Some(Duration::from_millis(200)));
+    assert_eq!(p.max_idle_time(), Some(Duration::from_millis(200)));
+    //using it restarts its idle time
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    drop(p.get().await.unwrap());
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(p.state().idle, 1);
+    assert_eq!(p.closed(CloseReason::IdleTimeout), 0);
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(p.closed(CloseReason::IdleTimeout), 1);
+    assert_eq!(p.state().connections, 0);
+    p.set_max_idle_time(None);
+    drop(p.get().await.unwrap());
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(p.state().idle, 1);
+}
+
+#[tokio::test]
+async fn test_builder_max_idle_time() {
+    let p = Pool::builder(TestManager {})
+        .max_open(1)
+        .max_idle_time(Duration::from_millis(100))
+        .build();
+    drop(p.get().await.unwrap());
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(p.closed(CloseReason::IdleTimeout), 1);
+    assert_eq!(p.state().connections, 0);
+}
+
+#[tokio::test]
+async fn test_builder_max_idle_time_wrap() {
+    //dropped by wrap(), which cannot bind the sweep to the wrapped manager
+    let p = Pool::builder(TestManager {})
+        .max_idle_time(Duration::from_millis(100))
+        .wrap(count_checks)
+        .build();
+    assert_eq!(p.max_idle_time(), None);
+    //kept by boxed()
+    let p: BoxPool<String, String> = Pool::builder(TestManager {})
+        .max_idle_time(Duration::from_millis(100))
+        .boxed()
+        .build();
+    assert_eq!(p.max_idle_time(), Some(Duration::from_millis(100)));
+    drop(p.get().await.unwrap());
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(p.closed(CloseReason::IdleTimeout), 1);
+}
+
+#[test]
+fn test_builder_max_idle_time_outside_runtime() {
+    let p = Pool::builder(TestManager {})
+        .max_open(1)
+        .max_idle_time(Duration::from_millis(100))
+        .build();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        //the sweep starts with the first get()
+        drop(p.get().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(p.closed(CloseReason::IdleTimeout), 1);
+    });
+}
+
+#[tokio::test]
+async fn test_conn_max_lifetime() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_conn_max_lifetime(Some(Duration::from_millis(100)));
+    assert_eq!(p.conn_max_lifetime(), Some(Duration::from_millis(100)));
+    drop(p.get().await.unwrap());
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    //expired while idle, replaced before it is handed out
+    let held = p.get().await.unwrap();
+    assert_eq!(p.closed(CloseReason::Expired), 1);
+    assert_eq!(p.state().created, 2);
+    //expired while in use, closed instead of recycled
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    drop(held);
+    assert_eq!(p.closed(CloseReason::Expired), 2);
+    assert_eq!(p.state().connections, 0);
+    p.set_conn_max_lifetime(None);
+    drop(p.get().await.unwrap());
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    drop(p.get().await.unwrap());
+    assert_eq!(p.closed(CloseReason::Expired), 2);
+    assert_eq!(p.state().created, 3);
+}
+
+#[tokio::test]
+async fn test_builder_conn_max_lifetime() {
+    let p = Pool::builder(TestManager {})
+        .max_open(1)
+        .conn_max_lifetime(Duration::from_millis(100))
+        .build();
+    assert_eq!(p.conn_max_lifetime(), Some(Duration::from_millis(100)));
+    let held = p.get().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    drop(held);
+    assert_eq!(p.closed(CloseReason::Expired), 1);
+    assert_eq!(p.state().connections, 0);
+}
+
+#[tokio::test]
+async fn test_drain() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(2);
+    let conn = p.get().await.unwrap();
+    assert!(p
+        .drain_timeout(false, Some(Duration::from_millis(10)))
+        .await
+        .is_err());
+    assert!(p.get().await.is_ok());
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(conn);
+    });
+    p.drain(true).await;
+    assert_eq!(p.state().in_use, 0);
+    assert!(p.is_draining());
+    assert!(p.get().await.is_err());
+}
+
+#[derive(Debug, Default)]
+pub struct SlowManager {
+    connects: std::sync::atomic::AtomicU64,
+}
+
+impl Manager for SlowManager {
+    type Connection = String;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        self.connects
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(String::new())
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_max_open_not_exceeded() {
+    let p = Pool::new(SlowManager::default());
+    p.set_max_open(2);
+    let mut handles = vec![];
+    for _ in 0..10 {
+        let pool = p.clone();
+        handles.push(tokio::spawn(async move {
+            let _conn = pool.get().await.unwrap();
+            assert!(pool.state().connections <= 2);
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    assert_eq!(p.state().connections, 2);
+    assert_eq!(p.state().idle, 2);
+}
+
+#[tokio::test]
+async fn test_take_inner_frees_slot() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    let mut conn = p.get().await.unwrap();
+    let raw = conn.inner.take();
+    assert_eq!(raw, Some(String::new()));
+    drop(conn);
+    assert_eq!(p.state().in_use, 0);
+    assert_eq!(p.state().connections, 0);
+    assert!(p.get_timeout(Some(Duration::from_secs(0))).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_cancelled_connect_frees_slot() {
+    let p = Pool::new(SlowManager::default());
+    p.set_max_open(1);
+    assert!(p
+        .get_timeout(Some(Duration::from_millis(10)))
+        .await
+        .is_err());
+    assert_eq!(p.state().connections, 0);
+    assert_eq!(p.state().in_use, 0);
+}
+
+#[cfg(feature = "test-util")]
+#[tokio::test]
+async fn test_pause_at_connect() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(2);
+    let connect = p.pause_at(AcquirePhase::Connect);
+    let wait = p.pause_at(AcquirePhase::Wait);
+    let mut handles = vec![];
+    for _ in 0..3 {
+        let pool = p.clone();
+        handles.push(tokio::spawn(async move {
+            let _conn = pool.get().await.unwrap();
+        }));
+    }
+    connect.wait_arrived(2).await;
+    wait.wait_arrived(1).await;
+    assert_eq!(p.state().connections, 2);
+    assert_eq!(p.state().waiting, 1);
+    drop(wait);
+    connect.release(2);
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    assert_eq!(connect.arrived(), 2);
+    assert_eq!(p.state().idle, 2);
+}
+
+static EVENTS: std::sync::Mutex<Vec<ConnectionEvent>> = std::sync::Mutex::new(Vec::new());
+
+#[tokio::test]
+async fn test_connection_events() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_connection_listener(Some(|e| EVENTS.lock().unwrap().push(*e)));
+    let mut conn = p.get().await.unwrap();
+    let id = conn.connection_id();
+    conn.inner = Some("error".to_string());
+    drop(conn);
+    assert!(p.get_timeout(Some(Duration::from_millis(10))).await.is_ok());
+    let states: Vec<(Option<ConnectionState>, ConnectionState)> = EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|e| e.pool_id == p.id() && e.connection_id == id)
+        .map(|e| (e.from, e.to))
+        .collect();
+    assert_eq!(
+        states,
+        vec![
+            (None, ConnectionState::Created),
+            (Some(ConnectionState::Created), ConnectionState::Idle),
+            (Some(ConnectionState::Idle), ConnectionState::Checking),
+            (Some(ConnectionState::Checking), ConnectionState::InUse),
+            (Some(ConnectionState::InUse), ConnectionState::Idle),
+            (Some(ConnectionState::Idle), ConnectionState::Checking),
+            (
+                Some(ConnectionState::Checking),
+                ConnectionState::Closed(CloseReason::CheckFailed)
+            ),
+        ]
+    );
+    assert_eq!(p.state().in_use, 0);
+}
+
+#[tokio::test]
+async fn test_avg_wait() {
+    let p = Pool::new(SlowManager::default());
+    assert_eq!(p.state().acquires, 0);
+    assert_eq!(p.state().avg_wait_us, 0);
+    p.set_max_open(1);
+    let conn = p.get().await.unwrap();
+    assert_eq!(p.state().acquires, 1);
+    let first = p.state().avg_wait_us;
+    assert!(first >= 50_000);
+    drop(conn);
+    //reusing the idle connection is fast and pulls the average down
+    _ = p.get().await.unwrap();
+    assert_eq!(p.state().acquires, 2);
+    assert!(p.state().avg_wait_us < first);
+}
+
+#[tokio::test]
+async fn test_prefer_healthy_connection() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(2);
+    p.set_check_failure_action(|_| FailureAction::KeepAndFail);
+    let mut flaky = p.get().await.unwrap();
+    let healthy = p.get().await.unwrap();
+    flaky.inner = Some("error".to_string());
+    drop(flaky);
+    drop(healthy);
+    //the flaky connection fails its check and is kept with a lower health
+    assert!(p.get().await.is_err());
+    let healthy = p.get().await.unwrap();
+    assert!(healthy.is_empty());
+    drop(healthy);
+    //the flaky connection is now first in the queue, but the healthy one is handed out
+    let conn = p.get().await.unwrap();
+    assert!(conn.is_empty());
+    assert!(conn.health() > 0.9);
+    assert_eq!(p.state().idle, 1);
+}
+
+#[tokio::test]
+async fn test_extensions() {
+    #[derive(Debug, PartialEq)]
+    struct TraceId(u64);
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    let mut conn = p.get().await.unwrap();
+    assert!(conn.extensions_mut().insert(TraceId(1)).is_none());
+    assert_eq!(conn.extensions_mut().insert(TraceId(2)), Some(TraceId(1)));
+    assert_eq!(conn.extensions().get::<TraceId>(), Some(&TraceId(2)));
+    conn.connection_extensions_mut()
+        .insert(String::from("tenant"));
+    drop(conn);
+    let mut conn = p.get().await.unwrap();
+    assert!(conn.extensions().is_empty());
+    assert_eq!(
+        conn.connection_extensions()
+            .get::<String>()
+            .map(|v| v.as_str()),
+        Some("tenant")
+    );
+    assert_eq!(
+        conn.connection_extensions_mut().remove::<String>(),
+        Some(String::from("tenant"))
+    );
+}
+
+#[tokio::test]
+async fn test_resize_and_wait() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    let arr = vec![
+        p.get().await.unwrap(),
+        p.get().await.unwrap(),
+        p.get().await.unwrap(),
+    ];
+    assert!(p
+        .resize_and_wait(1, Some(Duration::from_millis(10)))
+        .await
+        .is_err());
+    assert_eq!(p.state().connections, 3);
+    let p2 = p.clone();
+    let task = tokio::spawn(async move { p2.resize_and_wait(1, None).await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(arr);
+    assert!(task.await.unwrap().is_ok());
+    assert_eq!(p.state().connections, 1);
+    assert_eq!(p.closed(CloseReason::Resized), 2);
+}
+
+#[tokio::test]
+async fn test_max_open_unlimited() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open_unlimited();
+    assert_eq!(p.state().max_open, UNLIMITED);
+    let mut arr = vec![];
+    for _ in 0..100 {
+        arr.push(p.get_timeout(Some(Duration::from_secs(1))).await.unwrap());
+    }
+    assert_eq!(p.state().connections, 100);
+    drop(arr);
+    //idle connections are reused instead of creating new ones
+    for _ in 0..10 {
+        _ = p.get().await.unwrap();
+    }
+    assert_eq!(p.state().connections, 100);
+    p.set_max_open(10);
+    assert_eq!(p.state().connections, 10);
+}
+
+#[tokio::test]
+async fn test_reuse_wait() {
+    let p = Pool::new(SlowManager::default());
+    p.set_max_open(10);
+    p.set_reuse_wait(Some(Duration::from_millis(100)));
+    for _ in 0..3 {
+        _ = p.get().await.unwrap();
+    }
+    //idle connections are reused instead of filling the pool up to max_open
+    assert_eq!(p.state().connections, 1);
+    let conn = p.get().await.unwrap();
+    let p2 = p.clone();
+    let task = tokio::spawn(async move { p2.get().await.is_ok() });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(conn);
+    assert!(task.await.unwrap());
+    //the second get() waited for the returned connection instead of connecting
+    assert_eq!(p.state().connections, 1);
+}
+
+#[tokio::test]
+async fn test_for_each_idle() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    let arr = vec![p.get().await.unwrap(), p.get().await.unwrap()];
+    drop(arr);
+    let mut lens = vec![];
+    let visited = p
+        .for_each_idle(async |conn: &mut String| {
+            lens.push(conn.len());
+            tokio::task::yield_now().await;
+        })
+        .await;
+    assert_eq!(visited, 2);
+    assert_eq!(lens, vec![0, 0]);
+    assert_eq!(p.state().idle, 2);
+    assert_eq!(p.state().in_use, 0);
+}
+
+#[cfg(feature = "log")]
+static LOGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+#[cfg(feature = "log")]
+struct TestLogger;
+
+#[cfg(feature = "log")]
+impl log::Log for TestLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        LOGS.lock().unwrap().push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "log")]
+#[tokio::test]
+async fn test_state_logger() {
+    _ = log::set_logger(&TestLogger);
+    log::set_max_level(log::LevelFilter::Info);
+    let p = Pool::new(TestManager {});
+    let handle = p.spawn_state_logger(Duration::from_millis(10));
+    tokio::time::sleep(Duration::from_millis(25)).await;
+    let prefix = format!("pool {:x} state: ", p.id());
+    assert!(LOGS.lock().unwrap().iter().any(|v| v.starts_with(&prefix)));
+    drop(p);
+    //the logger stops once the pool is dropped
+    handle.await.unwrap();
+}
+
+static STARVED: std::sync::Mutex<Vec<StarvationEvent>> = std::sync::Mutex::new(Vec::new());
+
+#[tokio::test]
+async fn test_starvation() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_starvation_threshold(Some(Duration::from_millis(10)));
+    p.set_starvation_listener(Some(|e| STARVED.lock().unwrap().push(e.clone())));
+    p.set_fail_on_starvation(true);
+    let _held = p.get().await.unwrap();
+    //nested acquisition while the only connection is held
+    assert_eq!(p.get().await.unwrap_err(), PoolError::Starved);
+    let events: Vec<StarvationEvent> = STARVED
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|e| e.pool_id == p.id())
+        .cloned()
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].in_use, 1);
+    assert_eq!(events[0].waiting, 1);
+    assert!(events[0].oldest_wait >= Duration::from_millis(10));
+}
+
+pub struct CorrelationManager {}
+
+impl Manager for CorrelationManager {
+    type Connection = String;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(fast_pool::correlation_id().unwrap_or_default())
+    }
+
+    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        *conn = fast_pool::correlation_id().unwrap_or_default();
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_get_with_correlation_id() {
+    let p = Pool::new(CorrelationManager {});
+    p.set_max_open(1);
+    let conn = p.get_with("req-1", None).await.unwrap();
+    assert_eq!(conn.deref(), "req-1");
+    drop(conn);
+    assert_eq!(p.get().await.unwrap().deref(), "");
+    assert_eq!(fast_pool::correlation_id(), None);
+}
+
+#[derive(Default)]
+pub struct BlockingManager {
+    running: std::sync::atomic::AtomicU64,
+    max_running: std::sync::atomic::AtomicU64,
+}
+
+impl SyncManager for BlockingManager {
+    type Connection = String;
+    type Error = String;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        use std::sync::atomic::Ordering;
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(20));
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok(String::new())
+    }
+
+    fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        if !conn.is_empty() {
+            return Err(conn.clone());
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_sync_manager_bridge() {
+    let p = Pool::new(SyncManagerBridge::new(BlockingManager::default()).max_blocking(1));
+    p.set_max_open(4);
+    let mut handles = vec![];
+    for _ in 0..4 {
+        let pool = p.clone();
+        handles.push(tokio::spawn(async move {
+            let conn = pool.get().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(conn);
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    let m = p
+        .manager_as::<SyncManagerBridge<BlockingManager>>()
+        .unwrap();
+    assert_eq!(
+        m.inner()
+            .max_running
+            .load(std::sync::atomic::Ordering::SeqCst),
+        1
+    );
+    let mut conn = p.get().await.unwrap();
+    conn.push_str("error");
+    drop(conn);
+    //the failed check dropped the connection and a new one was connected
+    assert!(p.get().await.unwrap().is_empty());
+    //the SyncManager Error comes back wrapped
+    let p = Pool::new(SyncManagerBridge::new(BlockingManager::default()));
+    p.set_max_open(1);
+    p.set_check_failure_action(|_| FailureAction::DropAndFail);
+    let mut conn = p.get().await.unwrap();
+    conn.push_str("error");
+    drop(conn);
+    assert_eq!(
+        p.get().await.unwrap_err(),
+        PoolError::CheckFailed(BlockingError::Manager("error".to_string()))
+    );
+    let mut conn = p.get().await.unwrap();
+    assert_eq!(conn.inner.take().unwrap().into_inner(), Some(String::new()));
+}
+
+#[tokio::test]
+async fn test_closure_manager() {
+    let p = Pool::new(ClosureManager::new(
+        || async { Ok::<_, String>(String::new()) },
+        |conn: &mut String| {
+            let conn = conn.clone();
+            async move {
+                tokio::task::yield_now().await;
+                if conn.is_empty() {
+                    Ok(())
+                } else {
+                    Err(conn)
+                }
+            }
+        },
+    ));
+    p.set_max_open(1);
+    let mut conn = p.get().await.unwrap();
+    conn.push_str("error");
+    drop(conn);
+    assert!(p.get().await.unwrap().is_empty());
+    assert_eq!(p.closed(CloseReason::CheckFailed), 1);
+}
+
+#[tokio::test]
+async fn test_state_diff() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(2);
+    let before = p.state();
+    let mut arr = vec![p.get().await.unwrap(), p.get().await.unwrap()];
+    arr[0].inner = Some("error".to_string());
+    drop(arr);
+    _ = p.get().await.unwrap();
+    let delta = p.state().diff(&before);
+    assert_eq!(delta.created, 3);
+    assert_eq!(delta.closed, 1);
+    assert_eq!(delta.acquires, 3);
+    assert_eq!(p.state().diff(&p.state()), Default::default());
+}
+
+#[tokio::test]
+async fn test_recent_acquires() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    _ = p.get().await.unwrap();
+    assert!(p.recent_acquires().is_empty());
+    p.set_acquire_audit(2);
+    let conn = p.get().await.unwrap();
+    assert!(p
+        .get_timeout(Some(Duration::from_millis(10)))
+        .await
+        .is_err());
+    drop(conn);
+    p.set_max_open(2);
+    let _conn = p.get().await.unwrap();
+    let fresh = p.get().await.unwrap();
+    let records = p.recent_acquires();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].outcome, AcquireOutcome::Ok);
+    assert!(!records[0].fresh);
+    assert!(records[1].fresh);
+    assert_eq!(records[1].connection_id, Some(fresh.connection_id()));
+    p.set_acquire_audit(1);
+    assert_eq!(p.recent_acquires(), records[1..].to_vec());
+}
+
+static SATURATION: std::sync::Mutex<Vec<SaturationEvent>> = std::sync::Mutex::new(Vec::new());
+
+#[tokio::test]
+async fn test_saturation_alert() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_saturation_alert(Some(Duration::from_millis(20)));
+    p.set_saturation_listener(Some(|e| SATURATION.lock().unwrap().push(*e)));
+    let id = p.id();
+    let events = || -> Vec<SaturationEvent> {
+        SATURATION
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| match e {
+                SaturationEvent::Saturated { pool_id, .. } => *pool_id == id,
+                SaturationEvent::Recovered { pool_id, .. } => *pool_id == id,
+            })
+            .cloned()
+            .collect()
+    };
+    let held = p.get().await.unwrap();
+    let p2 = p.clone();
+    let waiter = tokio::spawn(async move { p2.get().await.is_ok() });
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(matches!(events()[..], [SaturationEvent::Saturated { .. }]));
+    drop(held);
+    assert!(waiter.await.unwrap());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(matches!(
+        events()[..],
+        [
+            SaturationEvent::Saturated { .. },
+            SaturationEvent::Recovered { .. }
+        ]
+    ));
+}
+
+#[tokio::test]
+async fn test_pool_group() {
+    let a = Pool::new(TestManager {});
+    let b = Pool::new(TestManager {});
+    a.set_max_open(2);
+    b.set_max_open(2);
+    let group = PoolGroup::new(vec![a.clone(), b.clone()]);
+    let _c1 = group.get().await.unwrap();
+    let _c2 = group.get().await.unwrap();
+    //least loaded spreads the load
+    assert_eq!(a.state().in_use, 1);
+    assert_eq!(b.state().in_use, 1);
+    group.set_strategy(GroupStrategy::RoundRobin);
+    assert_eq!(group.strategy(), GroupStrategy::RoundRobin);
+    let first = group.pick().unwrap().id();
+    let second = group.pick().unwrap().id();
+    assert_ne!(first, second);
+    assert_eq!(group.pick().unwrap().id(), first);
+    let empty: PoolGroup<TestManager> = PoolGroup::new(vec![]);
+    assert!(empty.get().await.is_err());
+}
+
+#[tokio::test]
+async fn test_partitioned_pool() {
+    let p = PartitionedPool::new(TestManager {}, 3, 4);
+    let max_open: Vec<u64> = p.partitions().iter().map(|v| v.state().max_open).collect();
+    assert_eq!(max_open, vec![2, 1, 1]);
+    //current_thread runtime, every get() starts from the same local partition
+    let local = p.local().id();
+    let mut arr = vec![];
+    for _ in 0..4 {
+        arr.push(p.get().await.unwrap());
+    }
+    let local_in_use = p.partitions().iter().find(|v| v.id() == local).unwrap();
+    assert_eq!(local_in_use.state().in_use, local_in_use.state().max_open);
+    //the other partitions were stolen from once the local one was full
+    assert!(p
+        .partitions()
+        .iter()
+        .all(|v| v.state().in_use == v.state().max_open));
+    assert!(p
+        .get_timeout(Some(Duration::from_millis(10)))
+        .await
+        .is_err());
+    drop(arr);
+    _ = p.get().await.unwrap();
+    assert_eq!(local_in_use.state().idle, local_in_use.state().max_open);
+}
+
+#[cfg(feature = "config-watch")]
+static RELOADS: std::sync::Mutex<Vec<ConfigReloadEvent>> = std::sync::Mutex::new(Vec::new());
+
+#[cfg(feature = "config-watch")]
+#[tokio::test]
+async fn test_watch_config_file() {
+    let path = std::env::temp_dir().join(format!("fast_pool_{}.toml", std::process::id()));
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    let mut config = p.config();
+    config.max_check_failures = 7;
+    std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+    let handle = p.watch_config_file(
+        &path,
+        Duration::from_millis(5),
+        Some(|e| RELOADS.lock().unwrap().push(e.clone())),
+    );
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(p.max_check_failures(), 7);
+    //an invalid file is reported and leaves the pool alone
+    std::thread::sleep(Duration::from_millis(10));
+    std::fs::write(&path, "max_open = 0").unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(p.config(), config);
+    let id = p.id();
+    let events: Vec<bool> = RELOADS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|e| match e {
+            ConfigReloadEvent::Applied { pool_id, .. } if *pool_id == id => Some(true),
+            ConfigReloadEvent::Failed { pool_id, .. } if *pool_id == id => Some(false),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(events, vec![true, false]);
+    drop(p);
+    handle.await.unwrap();
+    _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_scope() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    let scope = p.scope();
+    let a = scope.get().await.unwrap();
+    let b = scope.get().await.unwrap();
+    let c = scope.get().await.unwrap();
+    assert_eq!(a.lock().await.as_ref().unwrap().deref(), "");
+    drop(a);
+    let detached = c.detach().await.unwrap();
+    assert_eq!(scope.held(), 1);
+    assert_eq!(p.state().in_use, 2);
+    //a connection in use is not taken back
+    let guard = b.lock().await;
+    assert_eq!(scope.release_all(), 0);
+    assert_eq!(scope.held(), 1);
+    drop(guard);
+    drop(scope);
+    //the leaked connection went back to the pool with its scope
+    assert!(b.is_released());
+    assert_eq!(p.state().in_use, 1);
+    drop(detached);
+    assert_eq!(p.state().in_use, 0);
+}
+
+#[tokio::test]
+async fn test_error_code() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    let _c = p.get().await.unwrap();
+    let e = p
+        .get_timeout(Some(Duration::from_millis(1)))
+        .await
+        .unwrap_err();
+    assert_eq!(e, PoolError::Timeout);
+    assert_eq!(e.code(), ErrorCode::PoolTimeout);
+    assert_eq!(e.error_code(), Some(ErrorCode::PoolTimeout));
+    assert_eq!(e.kind(), ErrorKind::Pool);
+    p.drain_timeout(true, Some(Duration::from_millis(1)))
+        .await
+        .unwrap_err();
+    let e = p.get().await.unwrap_err();
+    assert_eq!(e.error_code(), Some(ErrorCode::PoolDraining));
+    //a message without a code, e.g. of the manager
+    assert_eq!("error".error_code(), None);
+    let e = PoolError::CheckFailed("error".to_string());
+    assert_eq!(e.code(), ErrorCode::CheckFailed);
+    assert_eq!(
+        PoolError::ConnectFailed("error".to_string()).code(),
+        ErrorCode::ConnectFailed
+    );
+    assert_eq!(e.kind(), ErrorKind::Check);
+    assert_eq!(e.to_string(), "CHECK_FAILED: error");
+    assert_eq!(e.into_manager_error(), Some("error".to_string()));
+    for code in ErrorCode::ALL {
+        assert_eq!(ErrorCode::parse(code.message()), Some(code));
+    }
+}
+
+#[test]
+fn test_error_code_round_trip() {
+    let errors = [
+        PoolError::ConnectFailed("refused: again".to_string()),
+        PoolError::CheckFailed("".to_string()),
+        PoolError::InvalidConfig("max_open must be greater than 0"),
+        PoolError::Timeout,
+        PoolError::Draining,
+        PoolError::PoolClosed,
+        PoolError::Starved,
+        PoolError::DrainTimeout,
+        PoolError::ResizeTimeout,
+        PoolError::WarmTimeout,
+        PoolError::GroupEmpty,
+        PoolError::ConnectTimeout,
+        PoolError::CheckTimeout,
+        PoolError::Cancelled,
+    ];
+    assert_eq!(errors.each_ref().map(|e| e.code()), ErrorCode::ALL);
+    for e in errors {
+        let message = e.to_string();
+        assert!(message.starts_with(e.code().as_str()), "{}", message);
+        assert_eq!(e.error_code(), Some(e.code()));
+        assert_eq!(message.error_code(), Some(e.code()));
+        assert_eq!(ErrorCode::parse(&message), Some(e.code()));
+    }
+}
+
+#[tokio::test]
+async fn test_burst() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_burst(1, Duration::from_millis(10));
+    let a = p.get().await.unwrap();
+    //within the threshold max_open still holds
+    assert!(p.get_timeout(Some(Duration::from_millis(5))).await.is_err());
+    let b = p.get().await.unwrap();
+    assert_eq!(p.state().connections, 2);
+    //the hard cap is max_open + 1
+    assert!(p
+        .get_timeout(Some(Duration::from_millis(30)))
+        .await
+        .is_err());
+    drop(b);
+    assert_eq!(p.closed(CloseReason::Burst), 1);
+    assert_eq!(p.state().connections, 1);
+    drop(a);
+    assert_eq!(p.state().idle, 1);
+}
+
+#[cfg(feature = "schedule")]
+#[tokio::test]
+async fn test_schedule_capacity() {
+    let schedule = CapacitySchedule::new(10)
+        .window(CapacityWindow::hours(22, 6, 2))
+        .window(CapacityWindow::hours(9, 18, 50))
+        .utc_offset(8 * 3600);
+    let at = |h: u64| std::time::UNIX_EPOCH + Duration::from_secs(h * 3600);
+    //hours in UTC, the windows in UTC+8
+    assert_eq!(schedule.max_open_at(at(15)), 2);
+    assert_eq!(schedule.max_open_at(at(21)), 2);
+    assert_eq!(schedule.max_open_at(at(22)), 10);
+    assert_eq!(schedule.max_open_at(at(2)), 50);
+    assert_eq!(schedule.max_open_at(at(10)), 10);
+
+    let p = Pool::new(TestManager {});
+    let handle = p.schedule_capacity(CapacitySchedule::new(4), Duration::from_millis(5));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(p.state().max_open, 4);
+    //a manual change holds until the schedule moves to another window
+    p.set_max_open(6);
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(p.state().max_open, 6);
+    handle.abort();
+}
+
+#[cfg(feature = "schedule")]
+#[tokio::test]
+async fn test_schedule_min_idle() {
+    let schedule = CapacitySchedule::new(10)
+        .default_min_idle(1)
+        .window(CapacityWindow::hours(22, 6, 2).min_idle(2));
+    let at = |h: u64| std::time::UNIX_EPOCH + Duration::from_secs(h * 3600);
+    assert_eq!(schedule.min_idle_at(at(23)), 2);
+    assert_eq!(schedule.min_idle_at(at(12)), 1);
+    assert_eq!(schedule.window_at(at(12)), None);
+
+    let p = Pool::new(TestManager {});
+    let schedule = CapacitySchedule::new(4).window(CapacityWindow::hours(0, 24, 4).min_idle(3));
+    let handle = p.schedule_capacity(schedule, Duration::from_millis(5));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(p.state().idle, 3);
+    //connections taken out are replaced on the next tick
+    let _held = p.get().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(p.state().idle, 3);
+    assert_eq!(p.state().connections, 4);
+    handle.abort();
+}
+
+#[tokio::test]
+async fn test_rates() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    for _ in 0..10 {
+        p.get().await.unwrap();
+    }
+    let _c = p.get().await.unwrap();
+    assert!(p.get_timeout(Some(Duration::ZERO)).await.is_err());
+    let window = Duration::from_secs(RATE_WINDOW_SECS);
+    let per_window = |rate: f64| (rate * RATE_WINDOW_SECS as f64).round() as u64;
+    let rates = p.rates(window);
+    assert_eq!(per_window(rates.acquires), 12);
+    assert_eq!(per_window(rates.connects), 1);
+    assert_eq!(per_window(rates.timeouts), 1);
+}
+
+pub struct SlowCheckManager {}
+
+impl Manager for SlowCheckManager {
+    type Connection = String;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(String::new())
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_max_checking() {
+    let p = Pool::new(SlowCheckManager {});
+    p.set_max_open(4);
+    p.set_max_checking(1);
+    let start = std::time::Instant::now();
+    let (a, b, waiting_check) = tokio::join!(p.get(), p.get(), async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        p.state().waiting_check
+    });
+    a.unwrap();
+    b.unwrap();
+    assert_eq!(waiting_check, 1);
+    //the checks ran one after the other
+    assert!(start.elapsed() >= Duration::from_millis(40));
+    assert_eq!(p.state().waiting_check, 0);
+}
+
+#[tokio::test]
+async fn test_wait_warm() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    p.wait_warm(3, Duration::from_secs(1)).await.unwrap();
+    assert_eq!(p.state().idle, 3);
+    assert_eq!(p.state().created, 3);
+    //already warm
+    p.wait_warm(2, Duration::from_secs(1)).await.unwrap();
+    assert_eq!(p.state().created, 3);
+    //more than max_open never gets warm
+    let e = p.wait_warm(4, Duration::from_millis(10)).await.unwrap_err();
+    assert_eq!(e.error_code(), Some(ErrorCode::WarmTimeout));
+}
+
+#[tokio::test]
+async fn test_wait_for_idle() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(2);
+    let a = p.get().await.unwrap();
+    let b = p.get().await.unwrap();
+    for v in [a, b] {
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            drop(v);
+        });
+    }
+    tokio::time::timeout(Duration::from_secs(1), p.wait_for_idle(2))
+        .await
+        .unwrap();
+    assert_eq!(p.state().idle, 2);
+    assert_eq!(p.state().created, 2);
+}
+
+#[tokio::test]
+async fn test_notified() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    let c = p.get().await.unwrap();
+    let notified = p.notified();
+    //the return before the first poll still counts
+    drop(c);
+    tokio::time::timeout(Duration::from_secs(1), notified)
+        .await
+        .unwrap();
+    assert!(
+        tokio::time::timeout(Duration::from_millis(10), p.notified())
+            .await
+            .is_err()
+    );
+}
+
+/// a hand written state machine holding an acquisition
+struct Checkout<'a> {
+    acquire: Acquire<'a, TestManager>,
+}
+
+impl std::future::Future for Checkout<'_> {
+    type Output = bool;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        std::pin::Pin::new(&mut self.acquire)
+            .poll(cx)
+            .map(|v| v.is_ok())
+    }
+}
+
+#[tokio::test]
+async fn test_acquire_future() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    let checkout = Checkout {
+        acquire: p.acquire(None),
+    };
+    assert!(checkout.await);
+    //outlives the pool handle it came from
+    let owned = p.clone().acquire_owned(None);
+    let conn = owned.await.unwrap();
+    assert!(p.acquire(Some(Duration::ZERO)).await.is_err());
+    drop(conn);
+    assert_eq!(p.state().created, 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_state_consistent() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(4);
+    let mut tasks = vec![];
+    for _ in 0..4 {
+        let p = p.clone();
+        tasks.push(tokio::spawn(async move {
+            for _ in 0..2000 {
+                drop(p.get().await.unwrap());
+            }
+        }));
+    }
+    while tasks.iter().any(|v| !v.is_finished()) {
+        let state = p.state();
+        assert!(state.idle + state.in_use <= state.connections, "{}", state);
+        tokio::task::yield_now().await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_state_consistent_under_churn() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(6);
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    //readers on their own threads, never yielding to the writers
+    let readers: Vec<_> = (0..2)
+        .map(|_| {
+            let (p, done) = (p.clone(), done.clone());
+            std::thread::spawn(move || {
+                let mut reads = 0u64;
+                while !done.load(std::sync::atomic::Ordering::SeqCst) {
+                    let state = p.state();
+                    assert!(state.idle + state.in_use <= state.connections, "{}", state);
+                    assert!(state.connections <= 6, "{}", state);
+                    reads += 1;
+                }
+                reads
+            })
+        })
+        .collect();
+    let mut tasks = vec![];
+    for i in 0..4 {
+        let p = p.clone();
+        tasks.push(tokio::spawn(async move {
+            for n in 0..2000u64 {
+                let a = p.get().await.unwrap();
+                let b = p.get_timeout(Some(Duration::ZERO)).await;
+                //shrinking closes idle connections while others come back
+                if i == 0 && n % 50 == 0 {
+                    p.set_max_open(if n % 100 == 0 { 2 } else { 6 });
+                }
+                drop(a);
+                drop(b);
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+    done.store(true, std::sync::atomic::Ordering::SeqCst);
+    for reader in readers {
+        assert!(reader.join().unwrap() > 0);
+    }
+    let state = p.state();
+    assert_eq!(state.in_use, 0);
+    assert_eq!(state.idle, state.connections);
+}
+
+#[tokio::test]
+async fn test_batch_returns() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    p.set_batch_returns(true);
+    let conns = vec![
+        p.get().await.unwrap(),
+        p.get().await.unwrap(),
+        p.get().await.unwrap(),
+    ];
+    drop(conns);
+    //parked returns are reused, not replaced by new connections
+    for _ in 0..10 {
+        let _a = p.get().await.unwrap();
+        let _b = p.get().await.unwrap();
+        let _c = p.get().await.unwrap();
+    }
+    assert_eq!(p.state().created, 3);
+    assert_eq!(p.state().idle, 3);
+    //a waiter gets a return right away
+    p.set_max_open(1);
+    let held = p.get().await.unwrap();
+    let p2 = p.clone();
+    let task = tokio::spawn(async move { p2.get().await.is_ok() });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(held);
+    assert!(tokio::time::timeout(Duration::from_secs(1), task)
+        .await
+        .unwrap()
+        .unwrap());
+    p.set_batch_returns(false);
+    assert_eq!(p.state().idle, 1);
+}
+
+#[tokio::test]
+async fn test_shutdown() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    let held = p.get().await.unwrap();
+    drop(p.get().await.unwrap());
+    assert!(p.shutdown(Some(Duration::from_millis(10))).await.is_err());
+    assert!(p.get().await.is_err());
+    assert_eq!(p.closed(CloseReason::Shutdown), 1);
+    //returned after the grace period, closed as well
+    drop(held);
+    assert_eq!(p.closed(CloseReason::Shutdown), 2);
+    assert_eq!(p.state().connections, 0);
+}
+
+#[tokio::test]
+async fn test_check_budget_floor() {
+    let p = Pool::new(SlowCheckManager {});
+    p.set_max_open(1);
+    drop(p.get().await.unwrap());
+    p.set_check_budget_floor(Some(Duration::from_millis(50)));
+    //a 20ms check would eat most of a 30ms budget, it is skipped
+    let start = std::time::Instant::now();
+    drop(
+        p.get_timeout(Some(Duration::from_millis(30)))
+            .await
+            .unwrap(),
+    );
+    assert!(start.elapsed() < Duration::from_millis(20));
+    //without a deadline the check runs
+    let start = std::time::Instant::now();
+    drop(p.get().await.unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    p.set_low_budget_action(LowBudgetAction::FailFast);
+    let e = p
+        .get_timeout(Some(Duration::from_millis(30)))
+        .await
+        .unwrap_err();
+    assert_eq!(e.error_code(), Some(ErrorCode::PoolTimeout));
+    assert_eq!(p.state().idle, 1);
+}
+
+static DISPOSED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// a connection whose drop blocks
+pub struct SlowDrop;
+
+impl Drop for SlowDrop {
+    fn drop(&mut self) {
+        std::thread::sleep(Duration::from_millis(30));
+        DISPOSED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+pub struct SlowDropManager {}
+
+impl Manager for SlowDropManager {
+    type Connection = SlowDrop;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(SlowDrop)
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        Err("error".to_string())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_background_disposal() {
+    let p = Pool::new(SlowDropManager {});
+    p.set_check_failure_action(|_| FailureAction::DropAndFail);
+    p.set_background_disposal(Some(1));
+    let start = std::time::Instant::now();
+    assert!(p.get().await.is_err());
+    assert!(start.elapsed() < Duration::from_millis(30));
+    //the slot is free before the connection is gone
+    assert_eq!(p.state().connections, 0);
+    assert_eq!(DISPOSED.load(std::sync::atomic::Ordering::SeqCst), 0);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(DISPOSED.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+static GOODBYES: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+static DISCONNECTED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// a manager saying goodbye asynchronously
+pub struct GoodbyeManager {
+    healthy: bool,
+}
+
+impl Manager for GoodbyeManager {
+    type Connection = String;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok("conn".to_string())
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        if self.healthy {
+            Ok(())
+        } else {
+            Err("error".to_string())
+        }
+    }
+
+    async fn disconnect(&self, conn: Self::Connection) {
+        assert_eq!(conn, "conn");
+        GOODBYES.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        DISCONNECTED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_disconnect() {
+    let goodbyes = || GOODBYES.load(std::sync::atomic::Ordering::SeqCst);
+    let disconnected = || DISCONNECTED.load(std::sync::atomic::Ordering::SeqCst);
+    //through a BoxManager, which must forward it
+    let p = Pool::new(BoxManager::new(GoodbyeManager { healthy: false }));
+    p.set_check_failure_action(|_| FailureAction::DropAndFail);
+    //awaited inline on the acquire path
+    assert!(p.get().await.is_err());
+    assert_eq!(goodbyes(), 1);
+    assert_eq!(disconnected(), 1);
+    p.set_background_disposal(Some(1));
+    assert!(p.get().await.is_err());
+    assert!(p.get().await.is_err());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(goodbyes(), 3);
+    assert_eq!(disconnected(), 3);
+    assert_eq!(p.state().connections, 0);
+
+    //closed by the max_idle_time sweep, the goodbye runs to the end
+    let p = Pool::new(GoodbyeManager { healthy: true });
+    drop(p.get().await.unwrap());
+    p.set_max_idle_time(Some(Duration::from_millis(20)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(p.closed(CloseReason::IdleTimeout), 1);
+    assert_eq!(goodbyes(), 4);
+    assert_eq!(disconnected(), 4);
+
+    //closed by shutdown, awaited before it returns
+    let p = Pool::new(GoodbyeManager { healthy: true });
+    drop(p.get().await.unwrap());
+    p.shutdown(None).await.unwrap();
+    assert_eq!(goodbyes(), 5);
+    assert_eq!(disconnected(), 5);
+
+    //closed on a sync path without a disposal thread: a resize, the pool dropped
+    let p = Pool::new(GoodbyeManager { healthy: true });
+    p.set_max_open(2);
+    let held = (p.get().await.unwrap(), p.get().await.unwrap());
+    drop(held);
+    p.set_max_open(1);
+    assert_eq!(p.closed(CloseReason::Resized), 1);
+    drop(p);
+    assert_eq!(goodbyes(), 5);
+}
+
+static DISPOSAL_THREADS: std::sync::Mutex<Vec<std::thread::ThreadId>> =
+    std::sync::Mutex::new(Vec::new());
+
+/// a manager remembering the thread of every goodbye
+pub struct ThreadManager {}
+
+impl Manager for ThreadManager {
+    type Connection = String;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok("conn".to_string())
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn disconnect(&self, _conn: Self::Connection) {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        DISPOSAL_THREADS
+            .lock()
+            .unwrap()
+            .push(std::thread::current().id());
+    }
+}
+
+#[tokio::test]
+async fn test_background_disposal_thread() {
+    let p = Pool::new(ThreadManager {});
+    p.set_max_open(3);
+    let held = vec![
+        p.get().await.unwrap(),
+        p.get().await.unwrap(),
+        p.get().await.unwrap(),
+    ];
+    drop(held);
+    p.set_background_disposal(Some(1));
+    //the same thread, with a new limit
+    p.set_background_disposal(Some(2));
+    assert!(p.background_disposal());
+    p.set_max_open(1);
+    assert_eq!(p.closed(CloseReason::Resized), 2);
+    //the last idle connection is queued as the pool goes away, its goodbye still happens
+    drop(p);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let threads = DISPOSAL_THREADS.lock().unwrap().clone();
+    assert_eq!(threads.len(), 3);
+    assert!(threads.iter().all(|v| *v == threads[0]));
+    assert_ne!(threads[0], std::thread::current().id());
+}
+
+#[tokio::test]
+async fn test_retry_budget() {
+    let failures = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(1));
+    let remaining = failures.clone();
+    let p = Pool::new(ClosureManager::new(
+        move || {
+            let failed = remaining
+                .fetch_update(
+                    std::sync::atomic::Ordering::SeqCst,
+                    std::sync::atomic::Ordering::SeqCst,
+                    |v| v.checked_sub(1),
+                )
+                .is_ok();
+            async move {
+                if failed {
+                    Err("refused".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+        },
+        |_: &mut ()| async { Ok(()) },
+    ));
+    p.set_max_open(2);
+    //no budget, the connect error is returned
+    assert!(p.get().await.is_err());
+    p.set_retry_budget(2, 2);
+    assert_eq!(p.retry_budget(), (2, 2));
+    assert_eq!(p.retry_tokens(), 2);
+    failures.store(2, std::sync::atomic::Ordering::SeqCst);
+    let held = p.get().await.unwrap();
+    assert_eq!(p.retry_tokens(), 0);
+    //the bucket is empty, the failure is not retried
+    failures.store(1, std::sync::atomic::Ordering::SeqCst);
+    assert!(p.get().await.is_err());
+    drop(held);
+    //successes refill it, one token every 2
+    drop(p.get().await.unwrap());
+    assert_eq!(p.retry_tokens(), 1);
+}
+
+#[tokio::test]
+async fn test_errors() {
+    let p = Pool::new(SlowDropManager {});
+    p.set_check_failure_action(|_| FailureAction::DropAndFail);
+    assert!(p.get().await.is_err());
+    assert!(p.errors().is_empty());
+    p.set_error_history(2);
+    assert!(p.get().await.is_err());
+    let errors = p.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::Check);
+    assert_eq!(errors[0].message, "CHECK_FAILED: error");
+    assert!(errors[0].connection_id.is_some());
+
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    p.set_error_history(1);
+    let _held = p.get().await.unwrap();
+    for _ in 0..2 {
+        let e = p.get_timeout(Some(Duration::ZERO)).await.unwrap_err();
+        assert_eq!(e.error_code(), Some(ErrorCode::PoolTimeout));
+    }
+    let errors = p.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::Pool);
+    assert_eq!(errors[0].connection_id, None);
+    assert_eq!(errors[0].message.error_code(), Some(ErrorCode::PoolTimeout));
+}
+
+#[tokio::test]
+async fn test_error_handler() {
+    let events = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let p = Pool::new(SlowDropManager {});
+    p.set_check_failure_action(|_| FailureAction::DropAndFail);
+    let received = events.clone();
+    let handler: ErrorHandler =
+        std::sync::Arc::new(move |e: &ErrorEvent| received.lock().unwrap().push(e.clone()));
+    p.set_error_handler(Some(handler.clone()));
+    assert!(p.get().await.is_err());
+
+    let timed_out = Pool::new(TestManager {});
+    timed_out.set_max_open(1);
+    timed_out.set_error_handler(Some(handler));
+    let _held = timed_out.get().await.unwrap();
+    assert!(timed_out.get_timeout(Some(Duration::ZERO)).await.is_err());
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].pool_id, p.id());
+    assert_eq!(events[0].kind, ErrorKind::Check);
+    assert!(events[0].connection_id.is_some());
+    assert_eq!(events[0].code, ErrorCode::CheckFailed);
+    assert_eq!(events[1].pool_id, timed_out.id());
+    assert_eq!(events[1].kind, ErrorKind::Pool);
+    assert_eq!(events[1].code, ErrorCode::PoolTimeout);
+    //the history is still off
+    assert!(p.errors().is_empty());
+}
+
+#[tokio::test]
+async fn test_diagnose() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(1);
+    let report = p.diagnose().await;
+    assert_eq!(report.status(), DiagnosticStatus::Pass, "{}", report);
+    assert_eq!(report.items.len(), 7);
+    //the test connection is kept
+    assert_eq!(p.state().idle, 1);
+    let held = p.get().await.unwrap();
+    let report = p.diagnose().await;
+    assert_eq!(report.status(), DiagnosticStatus::Warn);
+    assert_eq!(
+        report.get("connect").unwrap().detail,
+        "pool is full, skipped"
+    );
+    assert_eq!(report.get("check").unwrap().status, DiagnosticStatus::Warn);
+    drop(held);
+
+    let p = Pool::new(SlowDropManager {});
+    let report = p.diagnose().await;
+    assert_eq!(report.status(), DiagnosticStatus::Fail);
+    assert_eq!(
+        report.get("connect").unwrap().status,
+        DiagnosticStatus::Pass
+    );
+    assert_eq!(report.get("check").unwrap().detail, "CHECK_FAILED: error");
+    assert_eq!(p.state().connections, 0);
+}
+
+#[tokio::test]
+async fn test_check_timeout() {
+    let p = Pool::new(SlowCheckManager {});
+    p.set_check_failure_action(|_| FailureAction::DropAndFail);
+    p.set_check_timeout(Some(Duration::from_millis(5)));
+    let e = p.get().await.unwrap_err();
+    assert_eq!(e.error_code(), Some(ErrorCode::CheckTimeout));
+    assert_eq!(p.state().connections, 0);
+    p.set_check_timeout(None);
+    assert!(p.get().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_timeout_tuning() {
+    let p = Pool::new(TestManager {});
+    let tuning = TimeoutTuning {
+        min: Duration::from_millis(50),
+        interval: Duration::from_millis(10),
+        ..Default::default()
+    };
+    p.set_timeout_tuning(Some(tuning));
+    assert_eq!(p.timeout_tuning(), Some(tuning));
+    for _ in 0..20 {
+        drop(p.get().await.unwrap());
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    //fast checks, the timeout is held at min
+    assert_eq!(p.check_timeout(), Some(Duration::from_millis(50)));
+    //not enough connects yet
+    assert_eq!(p.connect_timeout(), None);
+    p.set_timeout_tuning(None);
+    assert_eq!(p.timeout_tuning(), None);
+}
+
+/// a manager wrapper counting the checks of the manager it wraps
+pub struct CountChecks<M> {
+    inner: M,
+    checks: std::sync::atomic::AtomicU64,
+}
+
+fn count_checks<M>(inner: M) -> CountChecks<M> {
+    CountChecks {
+        inner,
+        checks: Default::default(),
+    }
+}
+
+impl<M: Manager + Sync> Manager for CountChecks<M>
+where
+    M::Connection: Send,
+{
+    type Connection = M::Connection;
+    type Error = M::Error;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        self.inner.connect().await
+    }
+
+    async fn check(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        self.checks
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.inner.check(conn).await
+    }
+}
+
+#[tokio::test]
+async fn test_builder_wrap() {
+    let p = Pool::builder(TestManager {})
+        .wrap(count_checks)
+        .wrap(count_checks)
+        .max_open(2)
+        .build();
+    assert_eq!(p.state().max_open, 2);
+    drop(p.get().await.unwrap());
+    let outer = p
+        .manager_as::<CountChecks<CountChecks<TestManager>>>()
+        .unwrap();
+    assert_eq!(outer.checks.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert_eq!(
+        outer.inner.checks.load(std::sync::atomic::Ordering::SeqCst),
+        1
+    );
+
+    let p: BoxPool<String, String> = Pool::builder(TestManager {})
+        .wrap(count_checks)
+        .boxed()
+        .build();
+    assert!(p.get().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_builder_settings() {
+    let p = Pool::builder(TestManager {})
+        .max_open(3)
+        .connect_timeout(Duration::from_secs(1))
+        .check_timeout(Duration::from_millis(500))
+        .check_mode(CheckMode::IdleTime(Duration::from_secs(1)))
+        .max_checking(2)
+        .reuse_wait(Duration::from_millis(10))
+        .panic_policy(PanicPolicy::Check)
+        .conn_max_lifetime(Duration::from_secs(60))
+        .max_idle_time(Duration::from_secs(30))
+        .build_and_warm(2, Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(p.state().idle, 2);
+    let config = p.config();
+    assert_eq!(config.max_open, 3);
+    assert_eq!(config.connect_timeout, Some(Duration::from_secs(1)));
+    assert_eq!(config.check_timeout, Some(Duration::from_millis(500)));
+    assert_eq!(
+        config.check_mode,
+        CheckMode::IdleTime(Duration::from_secs(1))
+    );
+    assert_eq!(config.max_checking, 2);
+    assert_eq!(config.reuse_wait, Some(Duration::from_millis(10)));
+    assert_eq!(config.panic_policy, PanicPolicy::Check);
+    assert_eq!(config.conn_max_lifetime, Some(Duration::from_secs(60)));
+    assert_eq!(config.max_idle_time, Some(Duration::from_secs(30)));
+}
+
+#[tokio::test]
+async fn test_strict_builder() {
+    //max_open before the manager, wrapping keeps the type state
+    let p = StrictPoolBuilder::new()
+        .max_open(3)
+        .manager(TestManager {})
+        .wrap(count_checks)
+        .build();
+    assert_eq!(p.state().max_open, 3);
+    drop(p.get().await.unwrap());
+    let m = p.manager_as::<CountChecks<TestManager>>().unwrap();
+    assert_eq!(m.checks.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_check_mode_idle_time() {
+    let p = Pool::builder(TestManager {}).wrap(count_checks).build();
+    p.set_check_mode(CheckMode::IdleTime(Duration::from_millis(50)));
+    assert_eq!(
+        p.check_mode(),
+        CheckMode::IdleTime(Duration::from_millis(50))
+    );
+    let checks = || {
+        p.manager_as::<CountChecks<TestManager>>()
+            .unwrap()
+            .checks
+            .load(std::sync::atomic::Ordering::SeqCst)
+    };
+    //just created and just returned, no check
+    drop(p.get().await.unwrap());
+    drop(p.get().await.unwrap());
+    assert_eq!(checks(), 0);
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    drop(p.get().await.unwrap());
+    assert_eq!(checks(), 1);
+    p.set_check_mode(CheckMode::Always);
+    drop(p.get().await.unwrap());
+    assert_eq!(checks(), 2);
+}
+
+#[tokio::test]
+async fn test_check_mode_sample() {
+    let p = Pool::builder(TestManager {})
+        .wrap(count_checks)
+        .max_open(1)
+        .build();
+    p.set_check_mode(CheckMode::Sample(0.0));
+    assert_eq!(p.check_mode(), CheckMode::Sample(0.0));
+    let checks = || {
+        p.manager_as::<CountChecks<TestManager>>()
+            .unwrap()
+            .checks
+            .load(std::sync::atomic::Ordering::SeqCst)
+    };
+    for _ in 0..10 {
+        drop(p.get().await.unwrap());
+    }
+    assert_eq!(checks(), 0);
+    //adjusted at runtime
+    p.set_check_mode(CheckMode::Sample(0.5));
+    for _ in 0..1000 {
+        drop(p.get().await.unwrap());
+    }
+    assert!((300..700).contains(&checks()), "{}", checks());
+    p.set_check_mode(CheckMode::Sample(1.0));
+    assert_eq!(p.check_mode(), CheckMode::Always);
+    p.set_check_mode(CheckMode::Sample(2.0));
+    assert_eq!(p.check_mode(), CheckMode::Always);
+}
+
+#[tokio::test]
+async fn test_check_mode_sample_after_error() {
+    let refused = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    let checks = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+    let (r, c) = (refused.clone(), checks.clone());
+    let p = Pool::new(ClosureManager::new(
+        move || {
+            let refused = r.swap(false, std::sync::atomic::Ordering::SeqCst);
+            async move {
+                if refused {
+                    Err("refused".to_string())
+                } else {
+                    Ok(String::new())
+                }
+            }
+        },
+        move |_: &mut String| {
+            c.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async { Ok(()) }
+        },
+    ));
+    p.set_check_mode(CheckMode::Sample(0.0));
+    assert!(p.get().await.is_err());
+    //checked after the error, the passed check ends that
+    drop(p.get().await.unwrap());
+    assert_eq!(checks.load(std::sync::atomic::Ordering::SeqCst), 1);
+    drop(p.get().await.unwrap());
+    assert_eq!(checks.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_panic_policy() {
+    let p = Pool::builder(TestManager {}).wrap(count_checks).build();
+    p.set_check_mode(CheckMode::IdleTime(Duration::from_secs(60)));
+    let checks = || {
+        p.manager_as::<CountChecks<TestManager>>()
+            .unwrap()
+            .checks
+            .load(std::sync::atomic::Ordering::SeqCst)
+    };
+    let panic_with = |conn| {
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let _conn = conn;
+            panic!("in the middle of a request");
+        }));
+        assert!(r.is_err());
+    };
+    //recycled by default, like before the policy existed
+    assert_eq!(p.panic_policy(), PanicPolicy::Recycle);
+    panic_with(p.get().await.unwrap());
+    assert_eq!(p.closed(CloseReason::Panicked), 0);
+    assert_eq!(p.state().idle, 1);
+
+    p.set_panic_policy(PanicPolicy::Discard);
+    panic_with(p.get().await.unwrap());
+    assert_eq!(p.closed(CloseReason::Panicked), 1);
+    assert_eq!(p.state().connections, 0);
+
+    p.set_panic_policy(PanicPolicy::Check);
+    panic_with(p.get().await.unwrap());
+    assert_eq!(p.state().idle, 1);
+    //recently returned, but checked anyway
+    drop(p.get().await.unwrap());
+    assert_eq!(checks(), 1);
+    drop(p.get().await.unwrap());
+    assert_eq!(checks(), 1);
+
+    p.set_panic_policy(PanicPolicy::Recycle);
+    panic_with(p.get().await.unwrap());
+    drop(p.get().await.unwrap());
+    assert_eq!(checks(), 1);
+    assert_eq!(p.closed(CloseReason::Panicked), 1);
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_config_durations() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    p.set_burst(1, Duration::from_millis(1500));
+    p.set_check_mode(CheckMode::IdleTime(Duration::from_secs(300)));
+    let text = toml::to_string(&p.config()).unwrap();
+    assert!(text.contains("burst_after = \"1s 500ms\""), "{}", text);
+    assert!(text.contains("IdleTime = \"5m\""), "{}", text);
+    assert_eq!(toml::from_str::<PoolConfig>(&text).unwrap(), p.config());
+
+    let text = "reuse_wait = \"100ms\"\n".to_string()
+        + &text
+            .replace("burst_after = \"1s 500ms\"", "burst_after = 250")
+            .replace("IdleTime = \"5m\"", "IdleTime = \"30s\"");
+    let config: PoolConfig = toml::from_str(&text).unwrap();
+    assert_eq!(config.burst_after, Duration::from_millis(250));
+    assert_eq!(
+        config.check_mode,
+        CheckMode::IdleTime(Duration::from_secs(30))
+    );
+    assert_eq!(config.reuse_wait, Some(Duration::from_millis(100)));
+    assert!(toml::from_str::<PoolConfig>(&text.replace("250", "\"soon\"")).is_err());
+}
+
+#[tokio::test]
+async fn test_close_counts() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(2);
+    let before = p.state();
+    let a = p.get().await.unwrap();
+    let b = p.get().await.unwrap();
+    p.set_max_open(1);
+    drop(a);
+    drop(b);
+    //one taken out of the pool
+    _ = p.get().await.unwrap().inner.take();
+    let counts = p.close_counts();
+    assert_eq!(
+        counts,
+        CloseCounts {
+            resized: 1,
+            discarded: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(counts.total(), 2);
+    assert_eq!(counts.get(CloseReason::Resized), 1);
+    let delta = p.state().diff(&before);
+    assert_eq!(delta.closed, 2);
+    assert_eq!(delta.closed_by, counts);
+    for reason in CloseReason::ALL {
+        assert_eq!(p.closed(reason), counts.get(reason));
+    }
+}
+
+#[tokio::test]
+async fn test_meter() {
+    let p = Pool::new(TestManager {});
+    p.set_meter(Some(std::sync::Arc::new(UsageLimit(100))));
+    let mut conn = p.get().await.unwrap();
+    let id = conn.connection_id();
+    conn.record(60);
+    drop(conn);
+    //usage is kept across checkouts
+    let mut conn = p.get().await.unwrap();
+    assert_eq!(conn.connection_id(), id);
+    assert_eq!(conn.usage(), 60);
+    conn.record(40);
+    drop(conn);
+    assert_eq!(p.closed(CloseReason::Retired), 1);
+    assert_eq!(p.usage(), 100);
+    let conn = p.get().await.unwrap();
+    assert_ne!(conn.connection_id(), id);
+    assert_eq!(conn.usage(), 0);
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Duplex {
+    read: Vec<u8>,
+    write: Vec<u8>,
+}
+
+impl Splittable for Duplex {
+    type Read = Vec<u8>;
+    type Write = Vec<u8>;
+
+    fn split(self) -> (Vec<u8>, Vec<u8>) {
+        (self.read, self.write)
+    }
+
+    fn unsplit(read: Vec<u8>, write: Vec<u8>) -> Self {
+        Self { read, write }
+    }
+}
+
+#[tokio::test]
+async fn test_split() {
+    let p = Pool::new(ClosureManager::new(
+        || async { Ok::<_, String>(Duplex::default()) },
+        |_: &mut Duplex| async { Ok(()) },
+    ));
+    let conn = p.get().await.unwrap();
+    let id = conn.connection_id();
+    let (mut read, mut write) = conn.split();
+    assert_eq!(read.connection_id(), id);
+    let writer = tokio::spawn(async move {
+        write.push(1);
+        drop(write);
+    });
+    read.push(2);
+    writer.await.unwrap();
+    //one slot shared by both halves, returned once both are gone
+    assert_eq!(p.state().in_use, 1);
+    drop(read);
+    assert_eq!(p.state().in_use, 0);
+    let conn = p.get().await.unwrap();
+    assert_eq!(conn.connection_id(), id);
+    assert_eq!(
+        *conn,
+        Duplex {
+            read: vec![2],
+            write: vec![1]
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_take_all_idle() {
+    let p = Pool::new(TestManager {});
+    p.set_max_open(3);
+    let a = p.get().await.unwrap();
+    let b = p.get().await.unwrap();
+    let c = p.get().await.unwrap();
+    drop(a);
+    drop(b);
+    let taken = p.take_all_idle();
+    assert_eq!(taken.len(), 2);
+    let state = p.state();
+    assert_eq!((state.connections, state.idle, state.in_use), (1, 0, 1));
+    assert_eq!(p.closed(CloseReason::Discarded), 2);
+    drop(c);
+    assert_eq!(p.take_all_idle().len(), 1);
+    assert_eq!(p.state().connections, 0);
+}
+
+#[tokio::test]
+async fn test_handles() {
+    let p = Pool::new(TestManager {});
+    assert_eq!(p.handles(), 1);
+    let retained = p.clone();
+    let scope = p.scope();
+    assert_eq!(p.state().handles, 3);
+    drop(scope);
+    //a connection does not keep a handle
+    let conn = retained.get().await.unwrap();
+    drop(retained);
+    assert_eq!(p.handles(), 1);
+    drop(conn);
+    assert!(p.state().to_string().contains("handles: 1"));
 }
@@ -0,0 +1,39 @@
+use fast_pool::{CloseReason, Manager, Pool};
+use std::time::Duration;
+
+pub struct TestManager {}
+
+impl Manager for TestManager {
+    type Connection = String;
+    type Error = String;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        Ok(String::new())
+    }
+
+    async fn check(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+//own test binary, the signal handler is process wide
+#[cfg(unix)]
+#[tokio::test]
+async fn test_shutdown_on_ctrl_c() {
+    let p = Pool::new(TestManager {});
+    drop(p.get().await.unwrap());
+    let handle = p.shutdown_on_ctrl_c(Duration::from_secs(1));
+    //let the handler be installed
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let status = std::process::Command::new("kill")
+        .args(["-TERM", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(p.is_draining());
+    assert_eq!(p.closed(CloseReason::Shutdown), 1);
+}
@@ -0,0 +1,103 @@
+use fast_pool::plugin::TonicManager;
+use fast_pool::{FailureAction, Pool, PoolError};
+use http_body_util::BodyExt;
+use hyper::body::{Body, Bytes, Frame, Incoming};
+use hyper::header::HeaderValue;
+use hyper::{HeaderMap, Request, Response};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::net::TcpListener;
+use tonic::transport::Endpoint;
+
+/// a response body made of ready frames
+struct Frames(VecDeque<Frame<Bytes>>);
+
+impl Body for Frames {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Poll::Ready(self.0.pop_front().map(Ok))
+    }
+}
+
+/// a grpc health service by hand: the service "down" is NOT_SERVING, everything else SERVING
+async fn serve() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(|req: Request<Incoming>| async move {
+                    assert_eq!(req.uri().path(), "/grpc.health.v1.Health/Check");
+                    let body = req.into_body().collect().await?.to_bytes();
+                    //skip the 5 bytes grpc frame header
+                    let status = if &body[5..] == b"\x0a\x04down" { 2 } else { 1 };
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                    let frames = Frames(VecDeque::from([
+                        Frame::data(Bytes::from(vec![0, 0, 0, 0, 2, 0x08, status])),
+                        Frame::trailers(trailers),
+                    ]));
+                    let mut res = Response::new(frames);
+                    res.headers_mut()
+                        .insert("content-type", HeaderValue::from_static("application/grpc"));
+                    Ok::<_, hyper::Error>(res)
+                });
+                _ = hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl From<&str> for Error {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<tonic::transport::Error> for Error {
+    fn from(value: tonic::transport::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<tonic::Status> for Error {
+    fn from(value: tonic::Status) -> Self {
+        Self(value.message().to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_tonic_manager() {
+    let addr = serve().await;
+    let endpoint = Endpoint::from_shared(format!("http://{}", addr)).unwrap();
+    let p = Pool::new(TonicManager::<Error>::new(endpoint.clone()).health_check(""));
+    p.set_max_open(1);
+    for _ in 0..3 {
+        p.get().await.unwrap();
+    }
+    //the health checks reused the same channel
+    assert_eq!(p.state().created, 1);
+
+    let p = Pool::new(TonicManager::<Error>::new(endpoint).health_check("down"));
+    p.set_check_failure_action(|_| FailureAction::DropAndFail);
+    match p.get().await.unwrap_err() {
+        PoolError::CheckFailed(e) => assert_eq!(e.0, "NOT_SERVING"),
+        e => panic!("{:?}", e),
+    }
+}